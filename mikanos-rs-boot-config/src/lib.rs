@@ -0,0 +1,305 @@
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use core::fmt;
+use core::str::FromStr;
+
+use log::LevelFilter;
+use uefi::proto::console::gop::PixelFormat;
+
+/// Loader settings read from the boot config file.
+///
+/// The file consists of `key = value` lines. Blank lines and lines starting with `#` are ignored.
+///
+/// ```text
+/// kernel = \kernel.elf
+/// cmdline = loglevel=debug
+/// kernel_dir = \mikanos\kernels
+/// timeout = 5
+/// cmdline.debug.elf = loglevel=debug
+/// initrd = \mikanos\initrd.tar
+/// kaslr = on
+/// resolution = 1280x800
+/// pixel_format = bgr
+/// log_level = info
+/// memmap = \memmap
+/// ```
+#[derive(Debug)]
+pub struct BootConfig {
+    pub kernel_path: String,
+    pub cmdline: String,
+    /// Directory listed by the boot menu. No menu is shown if unset.
+    pub kernel_dir: Option<String>,
+    /// Seconds before the boot menu boots the default entry.
+    pub timeout: u64,
+    /// Command lines for specific boot menu entries, keyed by file name.
+    pub entry_cmdlines: BTreeMap<String, String>,
+    pub initrd_path: Option<String>,
+    pub kaslr: bool,
+    pub resolution: Option<(usize, usize)>,
+    pub pixel_format: Option<PixelFormat>,
+    pub log_level: LevelFilter,
+    /// Where to dump the memory map before exiting boot services, for debugging. Disabled by
+    /// default, so that booting doesn't write to the ESP.
+    pub memmap_path: Option<String>,
+}
+
+/// A line of the config file that was ignored.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConfigError<'a> {
+    /// The line has no `=`.
+    NotKeyValue,
+    /// The value of a known key can't be parsed, e.g. `timeout = soon`.
+    InvalidValue {
+        what: &'static str,
+        value: &'a str,
+    },
+    UnknownKey(&'a str),
+}
+
+impl fmt::Display for ConfigError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotKeyValue => write!(f, "expected `key = value`"),
+            Self::InvalidValue { what, value } => write!(f, "invalid {} {:?}", what, value),
+            Self::UnknownKey(key) => write!(f, "unknown key {:?}", key),
+        }
+    }
+}
+
+impl Default for BootConfig {
+    fn default() -> Self {
+        Self {
+            kernel_path: "\\kernel.elf".to_string(),
+            cmdline: String::new(),
+            kernel_dir: None,
+            timeout: 5,
+            entry_cmdlines: BTreeMap::new(),
+            initrd_path: None,
+            kaslr: false,
+            resolution: None,
+            pixel_format: None,
+            log_level: LevelFilter::Info,
+            memmap_path: None,
+        }
+    }
+}
+
+impl BootConfig {
+    /// Parses a config file. Unknown keys and malformed values are passed to `report` with their
+    /// 1-based line number and ignored.
+    pub fn parse<'a>(text: &'a str, mut report: impl FnMut(usize, ConfigError<'a>)) -> Self {
+        let mut config = Self::default();
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                report(lineno + 1, ConfigError::NotKeyValue);
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let mut invalid = |what| report(lineno + 1, ConfigError::InvalidValue { what, value });
+            match key {
+                "kernel" => config.kernel_path = value.to_string(),
+                "cmdline" => config.cmdline = value.to_string(),
+                "kernel_dir" => config.kernel_dir = Some(value.to_string()),
+                "timeout" => match value.parse() {
+                    Ok(timeout) => config.timeout = timeout,
+                    Err(_) => invalid("timeout"),
+                },
+                _ if key.starts_with("cmdline.") => {
+                    let name = &key["cmdline.".len()..];
+                    config
+                        .entry_cmdlines
+                        .insert(name.to_string(), value.to_string());
+                }
+                "initrd" => config.initrd_path = Some(value.to_string()),
+                "kaslr" => match parse_bool(value) {
+                    Some(kaslr) => config.kaslr = kaslr,
+                    None => invalid("boolean"),
+                },
+                "resolution" => match parse_resolution(value) {
+                    Some(resolution) => config.resolution = Some(resolution),
+                    None => invalid("resolution"),
+                },
+                "pixel_format" => match parse_pixel_format(value) {
+                    Some(pixel_format) => config.pixel_format = Some(pixel_format),
+                    None => invalid("pixel format"),
+                },
+                "log_level" => match LevelFilter::from_str(value) {
+                    Ok(level) => config.log_level = level,
+                    Err(_) => invalid("log level"),
+                },
+                "memmap" if value.is_empty() => config.memmap_path = None,
+                "memmap" => config.memmap_path = Some(value.to_string()),
+                _ => report(lineno + 1, ConfigError::UnknownKey(key)),
+            }
+        }
+        config
+    }
+
+    /// Returns the command line for the boot menu entry `name`, falling back to `cmdline`.
+    pub fn cmdline_for(&self, name: &str) -> &str {
+        self.entry_cmdlines
+            .get(name)
+            .map_or(self.cmdline.as_str(), |cmdline| cmdline.as_str())
+    }
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s.to_ascii_lowercase().as_str() {
+        "1" | "on" | "yes" | "true" => Some(true),
+        "0" | "off" | "no" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_resolution(s: &str) -> Option<(usize, usize)> {
+    let (horizontal, vertical) = s.split_once('x')?;
+    Some((
+        horizontal.trim().parse().ok()?,
+        vertical.trim().parse().ok()?,
+    ))
+}
+
+fn parse_pixel_format(s: &str) -> Option<PixelFormat> {
+    match s.to_ascii_lowercase().as_str() {
+        "rgb" => Some(PixelFormat::Rgb),
+        "bgr" => Some(PixelFormat::Bgr),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `text` and collects what was reported.
+    fn parse(text: &str) -> (BootConfig, Vec<(usize, ConfigError)>) {
+        let mut errors = Vec::new();
+        let config = BootConfig::parse(text, |lineno, err| errors.push((lineno, err)));
+        (config, errors)
+    }
+
+    #[test]
+    fn empty_file_gives_defaults() {
+        let (config, errors) = parse("");
+        assert!(errors.is_empty());
+        assert_eq!(config.kernel_path, "\\kernel.elf");
+        assert_eq!(config.cmdline, "");
+        assert_eq!(config.kernel_dir, None);
+        assert_eq!(config.timeout, 5);
+        assert_eq!(config.initrd_path, None);
+        assert!(!config.kaslr);
+        assert_eq!(config.resolution, None);
+        assert_eq!(config.pixel_format, None);
+        assert_eq!(config.log_level, LevelFilter::Info);
+        assert_eq!(config.memmap_path, None);
+    }
+
+    #[test]
+    fn parses_all_keys() {
+        let text = "\
+kernel = \\mikanos\\kernel.elf
+cmdline = loglevel=debug init=/bin/shell
+kernel_dir = \\mikanos\\kernels
+timeout = 10
+cmdline.debug.elf = loglevel=debug
+initrd = \\mikanos\\initrd.tar
+kaslr = ON
+resolution = 1280 x 800
+pixel_format = bgr
+log_level = warn
+memmap = \\mikanos\\memmap.csv
+";
+        let (config, errors) = parse(text);
+        assert!(errors.is_empty());
+        assert_eq!(config.kernel_path, "\\mikanos\\kernel.elf");
+        // Only the first `=` separates the key from the value.
+        assert_eq!(config.cmdline, "loglevel=debug init=/bin/shell");
+        assert_eq!(config.kernel_dir.as_deref(), Some("\\mikanos\\kernels"));
+        assert_eq!(config.timeout, 10);
+        assert_eq!(config.initrd_path.as_deref(), Some("\\mikanos\\initrd.tar"));
+        assert!(config.kaslr);
+        assert_eq!(config.resolution, Some((1280, 800)));
+        assert_eq!(config.pixel_format, Some(PixelFormat::Bgr));
+        assert_eq!(config.log_level, LevelFilter::Warn);
+        assert_eq!(config.memmap_path.as_deref(), Some("\\mikanos\\memmap.csv"));
+    }
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        let (config, errors) = parse("# timeout = 1\n\n   \n  # kernel = x\ntimeout = 2\n");
+        assert!(errors.is_empty());
+        assert_eq!(config.timeout, 2);
+        assert_eq!(config.kernel_path, "\\kernel.elf");
+    }
+
+    #[test]
+    fn empty_memmap_disables_dump() {
+        let (config, errors) = parse("memmap = \\memmap\nmemmap =\n");
+        assert!(errors.is_empty());
+        assert_eq!(config.memmap_path, None);
+    }
+
+    #[test]
+    fn reports_bad_lines_and_keeps_defaults() {
+        let text = "\
+timeout = soon
+kaslr = maybe
+resolution = 1280
+pixel_format = yuv
+log_level = loud
+no separator
+colour = blue
+";
+        let (config, errors) = parse(text);
+        assert_eq!(errors, [
+            (1, ConfigError::InvalidValue {
+                what: "timeout",
+                value: "soon"
+            }),
+            (2, ConfigError::InvalidValue {
+                what: "boolean",
+                value: "maybe"
+            }),
+            (3, ConfigError::InvalidValue {
+                what: "resolution",
+                value: "1280"
+            }),
+            (4, ConfigError::InvalidValue {
+                what: "pixel format",
+                value: "yuv"
+            }),
+            (5, ConfigError::InvalidValue {
+                what: "log level",
+                value: "loud"
+            }),
+            (6, ConfigError::NotKeyValue),
+            (7, ConfigError::UnknownKey("colour")),
+        ]);
+        assert_eq!(config.timeout, 5);
+        assert!(!config.kaslr);
+        assert_eq!(config.resolution, None);
+        assert_eq!(config.pixel_format, None);
+        assert_eq!(config.log_level, LevelFilter::Info);
+    }
+
+    #[test]
+    fn later_lines_override_earlier_ones() {
+        let (config, _) = parse("timeout = 1\ntimeout = 3\n");
+        assert_eq!(config.timeout, 3);
+    }
+
+    #[test]
+    fn entry_cmdline_falls_back_to_default() {
+        let (config, _) = parse("cmdline = quiet\ncmdline.debug.elf = loglevel=debug\n");
+        assert_eq!(config.cmdline_for("debug.elf"), "loglevel=debug");
+        assert_eq!(config.cmdline_for("kernel.elf"), "quiet");
+    }
+}
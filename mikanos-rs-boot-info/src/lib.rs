@@ -0,0 +1,148 @@
+#![no_std]
+
+use mikanos_rs_frame_buffer::FrameBufferInfo;
+use uefi::mem::memory_map::{MemoryMapOwned, MemoryType};
+
+/// "MIKANRS\0" in little endian.
+pub const BOOT_INFO_MAGIC: u64 = u64::from_le_bytes(*b"MIKANRS\0");
+/// Bump this whenever the layout of `BootInfo` changes.
+pub const BOOT_INFO_VERSION: u32 = 5;
+
+/// Upper bound for anything the loader places in memory for the kernel. The kernel identity-maps
+/// only the first 64 GiB of physical memory.
+pub const MAX_LOAD_ADDRESS: u64 = 64 * 1024 * 1024 * 1024;
+
+/// Memory type of the pages holding the initial ramdisk.
+pub const INITRD_MEMORY_TYPE: MemoryType = MemoryType::custom(0x8000_0000);
+/// Memory type of the pages holding the kernel's symbol and string tables.
+pub const KERNEL_SYMBOLS_MEMORY_TYPE: MemoryType = MemoryType::custom(0x8000_0001);
+
+/// A physical address range. An empty range means "not provided".
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PhysicalRange {
+    pub start: u64,
+    pub size: u64,
+}
+
+impl PhysicalRange {
+    pub const fn new(start: u64, size: u64) -> Self {
+        Self { start, size }
+    }
+
+    pub const fn empty() -> Self {
+        Self::new(0, 0)
+    }
+
+    pub const fn end(&self) -> u64 {
+        self.start + self.size
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+}
+
+/// Boot data handed from the loader to the kernel entry point.
+///
+/// New fields must be appended at the end, together with a bump of `BOOT_INFO_VERSION`. A kernel
+/// then still accepts boot info from a newer loader and reads only the fields it knows about.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BootInfo {
+    pub magic: u64,
+    pub version: u32,
+    pub size: u32,
+    pub frame_buffer: FrameBufferInfo,
+    pub memory_map: *const MemoryMapOwned,
+    /// Physical address of the ACPI RSDP, or 0 if not found. Points to an ACPI 2.0+ RSDP unless the
+    /// firmware only provides the ACPI 1.0 one.
+    pub acpi_rsdp: u64,
+    /// UTF-8 kernel command line (not NUL-terminated).
+    pub cmdline: PhysicalRange,
+    /// Initial ramdisk image, allocated with `INITRD_MEMORY_TYPE`.
+    pub initrd: PhysicalRange,
+    pub kernel_image: PhysicalRange,
+    /// Offset added to the kernel's link-time addresses. Non-zero only for relocatable kernels.
+    pub kernel_load_bias: u64,
+    /// Raw `Elf64_Sym` entries of the kernel's `.symtab`, allocated with
+    /// `KERNEL_SYMBOLS_MEMORY_TYPE`. Empty if the kernel is stripped.
+    pub kernel_symtab: PhysicalRange,
+    /// String table referenced by `kernel_symtab`.
+    pub kernel_strtab: PhysicalRange,
+    /// Physical address of the `EFI_SYSTEM_TABLE`. Boot services are gone by the time the kernel
+    /// runs, but the configuration table and runtime services are still usable.
+    pub efi_system_table: u64,
+    /// Physical address of the `EFI_RUNTIME_SERVICES` table. No virtual address map is set, so
+    /// the services must be called with the identity mapping in place.
+    pub efi_runtime_services: u64,
+}
+
+#[derive(Debug)]
+pub enum BootInfoError {
+    InvalidMagic(u64),
+    VersionTooOld { minimum: u32, found: u32 },
+    SizeTooSmall { minimum: u32, found: u32 },
+}
+
+impl BootInfo {
+    pub fn new(frame_buffer: FrameBufferInfo) -> Self {
+        Self {
+            magic: BOOT_INFO_MAGIC,
+            version: BOOT_INFO_VERSION,
+            size: core::mem::size_of::<Self>() as u32,
+            frame_buffer,
+            memory_map: core::ptr::null(),
+            acpi_rsdp: 0,
+            cmdline: PhysicalRange::empty(),
+            initrd: PhysicalRange::empty(),
+            kernel_image: PhysicalRange::empty(),
+            kernel_load_bias: 0,
+            kernel_symtab: PhysicalRange::empty(),
+            kernel_strtab: PhysicalRange::empty(),
+            efi_system_table: 0,
+            efi_runtime_services: 0,
+        }
+    }
+
+    /// Checks that the structure was built by a loader whose layout starts with the caller's one.
+    pub fn validate(&self) -> Result<(), BootInfoError> {
+        if self.magic != BOOT_INFO_MAGIC {
+            return Err(BootInfoError::InvalidMagic(self.magic));
+        }
+        if self.version < BOOT_INFO_VERSION {
+            return Err(BootInfoError::VersionTooOld {
+                minimum: BOOT_INFO_VERSION,
+                found: self.version,
+            });
+        }
+        let minimum_size = core::mem::size_of::<Self>() as u32;
+        if self.size < minimum_size {
+            return Err(BootInfoError::SizeTooSmall {
+                minimum: minimum_size,
+                found: self.size,
+            });
+        }
+        Ok(())
+    }
+
+    /// # Safety
+    ///
+    /// `memory_map` must point to a live memory map.
+    pub unsafe fn memory_map(&self) -> &'static MemoryMapOwned {
+        unsafe { &*self.memory_map }
+    }
+
+    /// # Safety
+    ///
+    /// `cmdline` must be empty or refer to valid UTF-8 in identity-mapped memory.
+    pub unsafe fn cmdline(&self) -> &'static str {
+        if self.cmdline.is_empty() {
+            return "";
+        }
+        let bytes = unsafe {
+            core::slice::from_raw_parts(self.cmdline.start as *const u8, self.cmdline.size as usize)
+        };
+        core::str::from_utf8(bytes).unwrap_or("")
+    }
+}
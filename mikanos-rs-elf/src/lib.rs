@@ -0,0 +1,512 @@
+#![cfg_attr(not(test), no_std)]
+
+use core::fmt;
+
+use goblin::elf::{Elf, header, program_header, reloc};
+
+#[derive(Debug)]
+pub enum ElfError {
+    NotElf,
+    UnsupportedClass(u8),
+    UnsupportedEndianness(u8),
+    Malformed(goblin::error::Error),
+    UnsupportedMachine(u16),
+    UnsupportedType(u16),
+    NoLoadableSegment,
+    /// `p_filesz` of the segment is larger than its `p_memsz`.
+    FileSizeExceedsMemorySize {
+        index: usize,
+        filesz: u64,
+        memsz: u64,
+    },
+    /// The file contents of the segment lie (partly) beyond the end of the file.
+    SegmentOutOfFile {
+        index: usize,
+    },
+    /// `p_vaddr + p_memsz` of the segment overflows.
+    SegmentAddressOverflow {
+        index: usize,
+    },
+    OverlappingSegments {
+        first: usize,
+        second: usize,
+    },
+    EntryOutsideImage(u64),
+    UnsupportedRelocation(u32),
+    /// The word patched by an `R_X86_64_RELATIVE` relocation is not inside a loadable segment.
+    RelocationOutsideImage(u64),
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotElf => write!(f, "not an ELF file"),
+            Self::UnsupportedClass(class) => {
+                write!(f, "unsupported ELF class {} (expected ELFCLASS64)", class)
+            }
+            Self::UnsupportedEndianness(data) => {
+                write!(
+                    f,
+                    "unsupported data encoding {} (expected little endian)",
+                    data
+                )
+            }
+            Self::Malformed(err) => write!(f, "malformed ELF file: {}", err),
+            Self::UnsupportedMachine(machine) => {
+                write!(f, "unsupported machine {} (expected x86_64)", machine)
+            }
+            Self::UnsupportedType(ty) => {
+                write!(
+                    f,
+                    "unsupported ELF type {} (expected ET_EXEC or ET_DYN)",
+                    ty
+                )
+            }
+            Self::NoLoadableSegment => write!(f, "no PT_LOAD segment"),
+            Self::FileSizeExceedsMemorySize {
+                index,
+                filesz,
+                memsz,
+            } => write!(
+                f,
+                "segment {}: p_filesz ({:#x}) exceeds p_memsz ({:#x})",
+                index, filesz, memsz
+            ),
+            Self::SegmentOutOfFile { index } => {
+                write!(
+                    f,
+                    "segment {}: contents extend beyond the end of file",
+                    index
+                )
+            }
+            Self::SegmentAddressOverflow { index } => {
+                write!(f, "segment {}: address range overflows", index)
+            }
+            Self::OverlappingSegments { first, second } => {
+                write!(f, "segments {} and {} overlap", first, second)
+            }
+            Self::EntryOutsideImage(entry) => {
+                write!(
+                    f,
+                    "entry point {:#x} is outside of loadable segments",
+                    entry
+                )
+            }
+            Self::UnsupportedRelocation(ty) => write!(f, "unsupported relocation type {}", ty),
+            Self::RelocationOutsideImage(offset) => {
+                write!(
+                    f,
+                    "relocation at {:#x} is outside of loadable segments",
+                    offset
+                )
+            }
+        }
+    }
+}
+
+/// Parses `elf_data` and checks that it is a kernel image the loader can handle.
+///
+/// On success, every `PT_LOAD` segment is guaranteed to lie within `elf_data`, to have
+/// `p_filesz <= p_memsz`, and not to overlap other loadable segments. Relocatable (`ET_DYN`) images
+/// only contain `R_X86_64_NONE` and `R_X86_64_RELATIVE` relocations, and the latter only patch words
+/// inside loadable segments.
+pub fn validate(elf_data: &[u8]) -> Result<Elf<'_>, ElfError> {
+    if elf_data.len() < header::SIZEOF_IDENT || &elf_data[..header::SELFMAG] != header::ELFMAG {
+        return Err(ElfError::NotElf);
+    }
+    if elf_data[header::EI_CLASS] != header::ELFCLASS64 {
+        return Err(ElfError::UnsupportedClass(elf_data[header::EI_CLASS]));
+    }
+    if elf_data[header::EI_DATA] != header::ELFDATA2LSB {
+        return Err(ElfError::UnsupportedEndianness(elf_data[header::EI_DATA]));
+    }
+
+    let prog = Elf::parse(elf_data).map_err(ElfError::Malformed)?;
+    if prog.header.e_machine != header::EM_X86_64 {
+        return Err(ElfError::UnsupportedMachine(prog.header.e_machine));
+    }
+    if prog.header.e_type != header::ET_EXEC && prog.header.e_type != header::ET_DYN {
+        return Err(ElfError::UnsupportedType(prog.header.e_type));
+    }
+
+    let loads = || {
+        prog.program_headers
+            .iter()
+            .enumerate()
+            .filter(|(_, phdr)| phdr.p_type == program_header::PT_LOAD)
+    };
+    if loads().next().is_none() {
+        return Err(ElfError::NoLoadableSegment);
+    }
+    for (index, phdr) in loads() {
+        if phdr.p_filesz > phdr.p_memsz {
+            return Err(ElfError::FileSizeExceedsMemorySize {
+                index,
+                filesz: phdr.p_filesz,
+                memsz: phdr.p_memsz,
+            });
+        }
+        match phdr.p_offset.checked_add(phdr.p_filesz) {
+            Some(end) if end <= elf_data.len() as u64 => {}
+            _ => return Err(ElfError::SegmentOutOfFile { index }),
+        }
+        if phdr.p_vaddr.checked_add(phdr.p_memsz).is_none() {
+            return Err(ElfError::SegmentAddressOverflow { index });
+        }
+    }
+    for (first, a) in loads() {
+        for (second, b) in loads().filter(|(i, _)| *i > first) {
+            if a.p_vaddr < b.p_vaddr + b.p_memsz && b.p_vaddr < a.p_vaddr + a.p_memsz {
+                return Err(ElfError::OverlappingSegments { first, second });
+            }
+        }
+    }
+    let entry = prog.entry;
+    if !loads().any(|(_, phdr)| phdr.p_vaddr <= entry && entry < phdr.p_vaddr + phdr.p_memsz) {
+        return Err(ElfError::EntryOutsideImage(entry));
+    }
+
+    if prog.header.e_type == header::ET_DYN {
+        let relocs = prog
+            .dynrelas
+            .iter()
+            .chain(prog.dynrels.iter())
+            .chain(prog.pltrelocs.iter());
+        for r in relocs {
+            if r.r_type != reloc::R_X86_64_NONE && r.r_type != reloc::R_X86_64_RELATIVE {
+                return Err(ElfError::UnsupportedRelocation(r.r_type));
+            }
+            if r.r_type == reloc::R_X86_64_RELATIVE && !contains_range(&prog, r.r_offset, 8) {
+                return Err(ElfError::RelocationOutsideImage(r.r_offset));
+            }
+        }
+    }
+
+    Ok(prog)
+}
+
+/// Returns whether the `len` bytes at link-time address `vaddr` lie within a single `PT_LOAD`
+/// segment's `[p_vaddr, p_vaddr + p_memsz)`.
+pub fn contains_range(prog: &Elf, vaddr: u64, len: u64) -> bool {
+    let Some(end) = vaddr.checked_add(len) else {
+        return false;
+    };
+    prog.program_headers.iter().any(|phdr| {
+        phdr.p_type == program_header::PT_LOAD
+            && phdr.p_vaddr <= vaddr
+            && phdr
+                .p_vaddr
+                .checked_add(phdr.p_memsz)
+                .is_some_and(|segment_end| end <= segment_end)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EHDR_SIZE: usize = 64;
+    const PHDR_SIZE: usize = 56;
+
+    struct Segment {
+        p_type: u32,
+        offset: u64,
+        vaddr: u64,
+        filesz: u64,
+        memsz: u64,
+    }
+
+    fn load(offset: u64, vaddr: u64, filesz: u64, memsz: u64) -> Segment {
+        Segment {
+            p_type: program_header::PT_LOAD,
+            offset,
+            vaddr,
+            filesz,
+            memsz,
+        }
+    }
+
+    /// Builds an ELF64 little-endian image with the given header fields and program headers,
+    /// zero-padded to `file_size` bytes.
+    fn build_elf(
+        e_type: u16,
+        machine: u16,
+        entry: u64,
+        segments: &[Segment],
+        file_size: usize,
+    ) -> Vec<u8> {
+        let mut buf = vec![0u8; file_size.max(EHDR_SIZE + PHDR_SIZE * segments.len())];
+        buf[..4].copy_from_slice(header::ELFMAG);
+        buf[header::EI_CLASS] = header::ELFCLASS64;
+        buf[header::EI_DATA] = header::ELFDATA2LSB;
+        buf[6] = 1; // EI_VERSION
+        buf[16..18].copy_from_slice(&e_type.to_le_bytes());
+        buf[18..20].copy_from_slice(&machine.to_le_bytes());
+        buf[20..24].copy_from_slice(&1u32.to_le_bytes());
+        buf[24..32].copy_from_slice(&entry.to_le_bytes());
+        buf[32..40].copy_from_slice(&(EHDR_SIZE as u64).to_le_bytes());
+        buf[52..54].copy_from_slice(&(EHDR_SIZE as u16).to_le_bytes());
+        buf[54..56].copy_from_slice(&(PHDR_SIZE as u16).to_le_bytes());
+        buf[56..58].copy_from_slice(&(segments.len() as u16).to_le_bytes());
+        buf[58..60].copy_from_slice(&64u16.to_le_bytes());
+        for (i, seg) in segments.iter().enumerate() {
+            let p = &mut buf[EHDR_SIZE + PHDR_SIZE * i..EHDR_SIZE + PHDR_SIZE * (i + 1)];
+            p[0..4].copy_from_slice(&seg.p_type.to_le_bytes());
+            p[4..8].copy_from_slice(&program_header::PF_R.to_le_bytes());
+            p[8..16].copy_from_slice(&seg.offset.to_le_bytes());
+            p[16..24].copy_from_slice(&seg.vaddr.to_le_bytes());
+            p[24..32].copy_from_slice(&seg.vaddr.to_le_bytes());
+            p[32..40].copy_from_slice(&seg.filesz.to_le_bytes());
+            p[40..48].copy_from_slice(&seg.memsz.to_le_bytes());
+            p[48..56].copy_from_slice(&0x1000u64.to_le_bytes());
+        }
+        buf
+    }
+
+    fn valid_kernel() -> Vec<u8> {
+        build_elf(
+            header::ET_EXEC,
+            header::EM_X86_64,
+            0x100000,
+            &[
+                load(0x1000, 0x100000, 0x100, 0x100),
+                load(0x2000, 0x101000, 0x80, 0x1000),
+            ],
+            0x2080,
+        )
+    }
+
+    #[test]
+    fn accepts_valid_kernel() {
+        let elf = valid_kernel();
+        let prog = validate(&elf).unwrap();
+        assert_eq!(prog.entry, 0x100000);
+    }
+
+    #[test]
+    fn accepts_relocatable_kernel() {
+        let elf = build_elf(
+            header::ET_DYN,
+            header::EM_X86_64,
+            0x10,
+            &[load(0x1000, 0, 0x100, 0x100)],
+            0x1100,
+        );
+        assert!(validate(&elf).is_ok());
+    }
+
+    #[test]
+    fn rejects_relocation_outside_image() {
+        // A PT_DYNAMIC inside the segment points to one RELA entry patching 0x1000, which is past
+        // the end of the segment.
+        let dynamic = Segment {
+            p_type: program_header::PT_DYNAMIC,
+            ..load(0x1040, 0x40, 0x40, 0x40)
+        };
+        let mut elf = build_elf(
+            header::ET_DYN,
+            header::EM_X86_64,
+            0x10,
+            &[load(0x1000, 0, 0x100, 0x100), dynamic],
+            0x1100,
+        );
+        let dyns: [(u64, u64); 4] = [
+            (goblin::elf::dynamic::DT_RELA, 0x80),
+            (goblin::elf::dynamic::DT_RELASZ, 24),
+            (goblin::elf::dynamic::DT_RELAENT, 24),
+            (goblin::elf::dynamic::DT_NULL, 0),
+        ];
+        for (i, (tag, val)) in dyns.iter().enumerate() {
+            let entry = 0x1040 + 16 * i;
+            elf[entry..entry + 8].copy_from_slice(&tag.to_le_bytes());
+            elf[entry + 8..entry + 16].copy_from_slice(&val.to_le_bytes());
+        }
+        let rela = [0x1000, reloc::R_X86_64_RELATIVE as u64, 0];
+        for (i, field) in rela.iter().enumerate() {
+            let offset = 0x1080 + 8 * i;
+            elf[offset..offset + 8].copy_from_slice(&field.to_le_bytes());
+        }
+        assert!(matches!(
+            validate(&elf),
+            Err(ElfError::RelocationOutsideImage(0x1000))
+        ));
+
+        // The same entry patching the last word of the segment is fine.
+        elf[0x1080..0x1088].copy_from_slice(&0xf8u64.to_le_bytes());
+        assert!(validate(&elf).is_ok());
+    }
+
+    #[test]
+    fn rejects_non_elf() {
+        assert!(matches!(validate(b""), Err(ElfError::NotElf)));
+        assert!(matches!(validate(&[0u8; 128]), Err(ElfError::NotElf)));
+    }
+
+    #[test]
+    fn rejects_32bit_class() {
+        let mut elf = valid_kernel();
+        elf[header::EI_CLASS] = header::ELFCLASS32;
+        assert!(matches!(validate(&elf), Err(ElfError::UnsupportedClass(1))));
+    }
+
+    #[test]
+    fn rejects_big_endian() {
+        let mut elf = valid_kernel();
+        elf[header::EI_DATA] = header::ELFDATA2MSB;
+        assert!(matches!(
+            validate(&elf),
+            Err(ElfError::UnsupportedEndianness(2))
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_program_headers() {
+        let elf = valid_kernel();
+        assert!(matches!(
+            validate(&elf[..EHDR_SIZE + 10]),
+            Err(ElfError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_wrong_machine() {
+        let elf = build_elf(
+            header::ET_EXEC,
+            header::EM_AARCH64,
+            0x100000,
+            &[load(0x1000, 0x100000, 0x100, 0x100)],
+            0x1100,
+        );
+        assert!(matches!(
+            validate(&elf),
+            Err(ElfError::UnsupportedMachine(header::EM_AARCH64))
+        ));
+    }
+
+    #[test]
+    fn rejects_object_file() {
+        let elf = build_elf(
+            header::ET_REL,
+            header::EM_X86_64,
+            0x100000,
+            &[load(0x1000, 0x100000, 0x100, 0x100)],
+            0x1100,
+        );
+        assert!(matches!(
+            validate(&elf),
+            Err(ElfError::UnsupportedType(header::ET_REL))
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_load_segment() {
+        let segment = Segment {
+            p_type: program_header::PT_NOTE,
+            ..load(0x1000, 0x100000, 0x10, 0x10)
+        };
+        let elf = build_elf(
+            header::ET_EXEC,
+            header::EM_X86_64,
+            0x100000,
+            &[segment],
+            0x1010,
+        );
+        assert!(matches!(validate(&elf), Err(ElfError::NoLoadableSegment)));
+    }
+
+    #[test]
+    fn rejects_filesz_larger_than_memsz() {
+        let elf = build_elf(
+            header::ET_EXEC,
+            header::EM_X86_64,
+            0x100000,
+            &[load(0x1000, 0x100000, 0x200, 0x100)],
+            0x1200,
+        );
+        assert!(matches!(
+            validate(&elf),
+            Err(ElfError::FileSizeExceedsMemorySize { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_segment_beyond_end_of_file() {
+        let elf = build_elf(
+            header::ET_EXEC,
+            header::EM_X86_64,
+            0x100000,
+            &[load(0x1000, 0x100000, 0x100, 0x100)],
+            0x1080,
+        );
+        assert!(matches!(
+            validate(&elf),
+            Err(ElfError::SegmentOutOfFile { index: 0 })
+        ));
+    }
+
+    #[test]
+    fn rejects_segment_offset_overflow() {
+        let elf = build_elf(
+            header::ET_EXEC,
+            header::EM_X86_64,
+            0x100000,
+            &[load(u64::MAX, 0x100000, 0x100, 0x100)],
+            0x1100,
+        );
+        assert!(matches!(
+            validate(&elf),
+            Err(ElfError::SegmentOutOfFile { index: 0 })
+        ));
+    }
+
+    #[test]
+    fn rejects_segment_address_overflow() {
+        let elf = build_elf(
+            header::ET_EXEC,
+            header::EM_X86_64,
+            u64::MAX - 0x10,
+            &[load(0x1000, u64::MAX - 0x20, 0x10, 0x100)],
+            0x1100,
+        );
+        assert!(matches!(
+            validate(&elf),
+            Err(ElfError::SegmentAddressOverflow { index: 0 })
+        ));
+    }
+
+    #[test]
+    fn rejects_overlapping_segments() {
+        let elf = build_elf(
+            header::ET_EXEC,
+            header::EM_X86_64,
+            0x100000,
+            &[
+                load(0x1000, 0x100000, 0x100, 0x2000),
+                load(0x2000, 0x101000, 0x80, 0x1000),
+            ],
+            0x2080,
+        );
+        assert!(matches!(
+            validate(&elf),
+            Err(ElfError::OverlappingSegments {
+                first: 0,
+                second: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_entry_outside_image() {
+        let elf = build_elf(
+            header::ET_EXEC,
+            header::EM_X86_64,
+            0x200000,
+            &[load(0x1000, 0x100000, 0x100, 0x100)],
+            0x1100,
+        );
+        assert!(matches!(
+            validate(&elf),
+            Err(ElfError::EntryOutsideImage(0x200000))
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+//! Compares the row-oriented drawing paths with per-pixel writes.
+//!
+//! Run with `cargo bench -p mikanos-rs-frame-buffer`.
+#![feature(test)]
+
+extern crate test;
+
+use mikanos_rs_frame_buffer::{FrameBufferWriter, MemoryBuffer, PixelColor, PixelLayout, Rect};
+use test::{Bencher, black_box};
+
+const WIDTH: usize = 1280;
+const HEIGHT: usize = 800;
+
+fn new_buffer() -> MemoryBuffer {
+    MemoryBuffer::new(WIDTH, HEIGHT, PixelLayout::BGR)
+}
+
+/// What `write_pixel` used to do: match the pixel format and write bytes for every pixel.
+fn write_pixel_bytes(buffer: &mut MemoryBuffer, x: usize, y: usize, (r, g, b): (u8, u8, u8)) {
+    let pixel_idx = buffer.get_pixels_per_scan_line() * y + x;
+    let pixel_layout = buffer.get_pixel_layout();
+    let p = buffer.as_slice_mut();
+    match pixel_layout {
+        PixelLayout::RGB => {
+            p[4 * pixel_idx] = r;
+            p[4 * pixel_idx + 1] = g;
+            p[4 * pixel_idx + 2] = b;
+        }
+        PixelLayout::BGR => {
+            p[4 * pixel_idx] = b;
+            p[4 * pixel_idx + 1] = g;
+            p[4 * pixel_idx + 2] = r;
+        }
+        // Bitmask layouts, which the byte-wise code never handled.
+        layout => {
+            let pixel = layout.encode(&PixelColor::new(r, g, b));
+            p[4 * pixel_idx..4 * pixel_idx + 4].copy_from_slice(&pixel.to_le_bytes());
+        }
+    }
+}
+
+#[bench]
+fn fill_per_pixel_bytes(b: &mut Bencher) {
+    let mut buffer = new_buffer();
+    b.iter(|| {
+        for x in 0..WIDTH {
+            for y in 0..HEIGHT {
+                write_pixel_bytes(&mut buffer, x, y, black_box((0x12, 0x34, 0x56)));
+            }
+        }
+    });
+}
+
+#[bench]
+fn fill_write_pixel(b: &mut Bencher) {
+    let mut buffer = new_buffer();
+    let color = PixelColor::new(0x12, 0x34, 0x56);
+    b.iter(|| {
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                buffer.write_pixel(x, y, black_box(&color));
+            }
+        }
+    });
+}
+
+#[bench]
+fn fill_rows(b: &mut Bencher) {
+    let mut buffer = new_buffer();
+    let color = PixelColor::new(0x12, 0x34, 0x56);
+    b.iter(|| buffer.fill(black_box(&color)));
+}
+
+#[bench]
+fn copy_per_pixel(b: &mut Bencher) {
+    let src = new_buffer();
+    let mut dst = new_buffer();
+    b.iter(|| {
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                dst.write_pixel(x, y, &src.read_pixel(x, y));
+            }
+        }
+    });
+}
+
+#[bench]
+fn copy_blit(b: &mut Bencher) {
+    let src = new_buffer();
+    let mut dst = new_buffer();
+    b.iter(|| dst.blit(black_box(&src), src.bounds(), 0, 0));
+}
+
+#[bench]
+fn scroll_copy_within(b: &mut Bencher) {
+    let mut buffer = new_buffer();
+    b.iter(|| buffer.copy_within(Rect::new(0, 16, WIDTH, HEIGHT - 16), 0, 0));
+}
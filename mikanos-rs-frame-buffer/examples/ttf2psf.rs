@@ -0,0 +1,57 @@
+//! Renders the Latin-1 range of a monospaced TrueType font into an 8x16 PSF2 font.
+//!
+//! `cargo run -p mikanos-rs-frame-buffer --example ttf2psf -- <font.ttf> <out.psf> [size]`
+//!
+//! This is how `fonts/default-8x16.psf` was made. Glyphs are thresholded to one bit per pixel and
+//! placed so that the baseline lands where `Psf2Font::ascent` expects it.
+use std::{env, fs, process};
+
+const WIDTH: usize = 8;
+const HEIGHT: usize = 16;
+const ASCENT: i32 = (HEIGHT - HEIGHT / 4) as i32;
+const NUM_GLYPHS: usize = 256;
+// Minimum coverage for a pixel to be set. Lower than half keeps thin strokes connected.
+const THRESHOLD: u8 = 96;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("usage: {} <font.ttf> <out.psf> [size]", args[0]);
+        process::exit(1);
+    }
+    let size: f32 = args
+        .get(3)
+        .map_or(13.0, |s| s.parse().expect("invalid size"));
+    let ttf = fs::read(&args[1]).expect("cannot read the font");
+    let font = fontdue::Font::from_bytes(ttf, fontdue::FontSettings::default())
+        .expect("cannot parse the font");
+
+    // Header: magic, version, header size, flags, glyph count, bytes per glyph, height, width.
+    let mut psf = vec![0x72, 0xb5, 0x4a, 0x86];
+    for field in [0, 32, 0, NUM_GLYPHS, HEIGHT, HEIGHT, WIDTH] {
+        psf.extend_from_slice(&(field as u32).to_le_bytes());
+    }
+
+    for code in 0..NUM_GLYPHS as u32 {
+        let mut glyph = [0u8; HEIGHT];
+        let c = char::from_u32(code).unwrap();
+        if !c.is_control() {
+            let (metrics, coverage) = font.rasterize(c, size);
+            // Center the glyph in the cell, since the font's advance isn't exactly 8 pixels.
+            let left = (WIDTH as i32 - metrics.advance_width.round() as i32) / 2 + metrics.xmin;
+            let top = ASCENT - metrics.ymin - metrics.height as i32;
+            for y in 0..metrics.height {
+                for x in 0..metrics.width {
+                    let (px, py) = (left + x as i32, top + y as i32);
+                    let inside =
+                        (0..WIDTH as i32).contains(&px) && (0..HEIGHT as i32).contains(&py);
+                    if inside && coverage[y * metrics.width + x] >= THRESHOLD {
+                        glyph[py as usize] |= 0x80 >> px;
+                    }
+                }
+            }
+        }
+        psf.extend_from_slice(&glyph);
+    }
+    fs::write(&args[2], psf).expect("cannot write the PSF file");
+}
@@ -0,0 +1,72 @@
+use crate::{BlendMode, FrameBufferWriter, PixelColor, Rect};
+
+const CURSOR_WIDTH: usize = 15;
+const CURSOR_HEIGHT: usize = 24;
+const CURSOR: [&str; CURSOR_HEIGHT] = [
+    "@              ",
+    "@@             ",
+    "@.@            ",
+    "@..@           ",
+    "@...@          ",
+    "@....@         ",
+    "@.....@        ",
+    "@......@       ",
+    "@.......@      ",
+    "@........@     ",
+    "@.........@    ",
+    "@..........@   ",
+    "@...........@  ",
+    "@............@ ",
+    "@......@@@@@@@@",
+    "@......@       ",
+    "@....@@.@      ",
+    "@...@ @.@      ",
+    "@..@   @.@     ",
+    "@.@    @.@     ",
+    "@@      @.@    ",
+    "@       @.@    ",
+    "         @.@   ",
+    "         @@@   ",
+];
+
+const SHADOW_OFFSET: isize = 2;
+
+/// Draws the mouse cursor with its tip at `(x, y)`, clipped to the buffer.
+pub fn draw_cursor<W: FrameBufferWriter + ?Sized>(buffer: &mut W, x: isize, y: isize) {
+    let black = &PixelColor::new(0, 0, 0);
+    let white = &PixelColor::new(255, 255, 255);
+    let shadow = &PixelColor::rgba(0, 0, 0, 80);
+    for (dy, start, len, _) in cursor_runs() {
+        let rect = Rect::new(
+            x + start as isize + SHADOW_OFFSET,
+            y + dy as isize + SHADOW_OFFSET,
+            len,
+            1,
+        );
+        buffer.blend_rect(rect, shadow, BlendMode::SourceOver);
+    }
+    for (dy, start, len, c) in cursor_runs() {
+        let color = if c == b'@' { black } else { white };
+        buffer.draw_hline(x + start as isize, y + dy as isize, len, color);
+    }
+}
+
+/// Yields `(row, start, len, char)` for each run of same non-blank characters in the cursor, so
+/// that it can be drawn one clipped line at a time.
+fn cursor_runs() -> impl Iterator<Item = (usize, usize, usize, u8)> {
+    CURSOR.iter().enumerate().flat_map(|(dy, row)| {
+        let row = row.as_bytes();
+        let mut start = 0;
+        core::iter::from_fn(move || {
+            while start < CURSOR_WIDTH {
+                let c = row[start];
+                let len = row[start..].iter().take_while(|&&b| b == c).count();
+                start += len;
+                if c != b' ' {
+                    return Some((dy, start - len, len, c));
+                }
+            }
+            None
+        })
+    })
+}
@@ -0,0 +1,120 @@
+use core::slice;
+use uefi::proto::console::gop::{GraphicsOutput, PixelBitmask, PixelFormat};
+
+use crate::{FrameBufferWriter, PixelLayout};
+
+impl PixelLayout {
+    /// Returns `None` for `PixelFormat::BltOnly`, which has no linear frame buffer.
+    pub fn new(pixel_format: PixelFormat, bitmask: Option<PixelBitmask>) -> Option<Self> {
+        match pixel_format {
+            PixelFormat::Rgb => Some(Self::RGB),
+            PixelFormat::Bgr => Some(Self::BGR),
+            PixelFormat::Bitmask => {
+                let bitmask = bitmask?;
+                Some(Self::from_masks(bitmask.red, bitmask.green, bitmask.blue))
+            }
+            PixelFormat::BltOnly => None,
+        }
+    }
+}
+
+/// Where the GOP frame buffer is and how it is laid out, as handed from the loader to the kernel.
+///
+/// This is plain data. Draw through a `FrameBuffer` created from it.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FrameBufferInfo {
+    frame_buffer: *mut u8,
+    pixels_per_scanline: usize,
+    horizontal_resolution: usize,
+    vertical_resolution: usize,
+    pixel_format: PixelFormat,
+    pixel_layout: PixelLayout,
+}
+
+impl FrameBufferInfo {
+    /// Returns `None` if the current mode has no linear frame buffer (`PixelFormat::BltOnly`).
+    pub fn new(gop: &mut GraphicsOutput) -> Option<Self> {
+        let mode_info = gop.current_mode_info();
+        let (horizontal, vertical) = mode_info.resolution();
+        let pixel_format = mode_info.pixel_format();
+        let pixel_layout = PixelLayout::new(pixel_format, mode_info.pixel_bitmask())?;
+
+        Some(Self {
+            frame_buffer: gop.frame_buffer().as_mut_ptr(),
+            pixels_per_scanline: mode_info.stride(),
+            horizontal_resolution: horizontal,
+            vertical_resolution: vertical,
+            pixel_format,
+            pixel_layout,
+        })
+    }
+
+    pub fn get_pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    pub fn get_pixel_layout(&self) -> PixelLayout {
+        self.pixel_layout
+    }
+
+    pub fn get_pixels_per_scan_line(&self) -> usize {
+        self.pixels_per_scanline
+    }
+
+    pub fn get_horizontal_resolution(&self) -> usize {
+        self.horizontal_resolution
+    }
+
+    pub fn get_vertical_resolution(&self) -> usize {
+        self.vertical_resolution
+    }
+}
+
+/// The GOP frame buffer, borrowed for drawing.
+pub struct FrameBuffer<'a> {
+    pixels: &'a mut [u32],
+    info: FrameBufferInfo,
+}
+
+impl FrameBuffer<'_> {
+    /// # Safety
+    ///
+    /// The frame buffer described by `info` must be mapped, and nothing else may access it while
+    /// the returned value is alive.
+    pub unsafe fn new(info: FrameBufferInfo) -> Self {
+        let ptr = info.frame_buffer as *mut u32;
+        debug_assert!(ptr.is_aligned());
+        let len = info.pixels_per_scanline * info.vertical_resolution;
+        Self {
+            pixels: unsafe { slice::from_raw_parts_mut(ptr, len) },
+            info,
+        }
+    }
+}
+
+impl FrameBufferWriter for FrameBuffer<'_> {
+    fn as_pixels(&self) -> &[u32] {
+        self.pixels
+    }
+
+    fn as_pixels_mut(&mut self) -> &mut [u32] {
+        self.pixels
+    }
+
+    fn get_pixel_layout(&self) -> PixelLayout {
+        self.info.pixel_layout
+    }
+
+    fn get_pixels_per_scan_line(&self) -> usize {
+        self.info.pixels_per_scanline
+    }
+
+    fn get_horizontal_resolution(&self) -> usize {
+        self.info.horizontal_resolution
+    }
+
+    fn get_vertical_resolution(&self) -> usize {
+        self.info.vertical_resolution
+    }
+}
@@ -0,0 +1,219 @@
+//! Decoders for wallpapers and other images, producing a `MemoryBuffer` that can be blitted.
+//!
+//! Alpha is ignored: images are drawn opaque.
+use core::fmt;
+
+use crate::{FrameBufferWriter, MemoryBuffer, PixelColor, PixelLayout};
+
+/// Larger images are rejected rather than risking a huge allocation.
+const MAX_DIMENSION: usize = 16384;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ImageError {
+    UnknownFormat,
+    Truncated,
+    InvalidHeader,
+    /// A valid image using a feature the decoder doesn't support, e.g. compressed BMP.
+    Unsupported,
+    TooLarge,
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownFormat => write!(f, "not a BMP or QOI image"),
+            Self::Truncated => write!(f, "the image is truncated"),
+            Self::InvalidHeader => write!(f, "invalid image header"),
+            Self::Unsupported => write!(f, "unsupported image format"),
+            Self::TooLarge => write!(f, "the image is too large"),
+        }
+    }
+}
+
+/// Decodes a BMP or QOI image, telling them apart by their signature.
+pub fn decode_image(data: &[u8], pixel_layout: PixelLayout) -> Result<MemoryBuffer, ImageError> {
+    if data.starts_with(b"BM") {
+        decode_bmp(data, pixel_layout)
+    } else if data.starts_with(b"qoif") {
+        decode_qoi(data, pixel_layout)
+    } else {
+        Err(ImageError::UnknownFormat)
+    }
+}
+
+/// Reads the width and height from a BMP or QOI header without decoding the pixels, e.g. to refuse
+/// images too large to allocate.
+pub fn image_size(data: &[u8]) -> Result<(usize, usize), ImageError> {
+    if data.starts_with(b"BM") {
+        bmp_size(data).map(|(width, height, _)| (width, height))
+    } else if data.starts_with(b"qoif") {
+        qoi_size(data)
+    } else {
+        Err(ImageError::UnknownFormat)
+    }
+}
+
+fn read_u16_le(data: &[u8], offset: usize) -> Result<u16, ImageError> {
+    let bytes = data.get(offset..offset + 2).ok_or(ImageError::Truncated)?;
+    Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u32_le(data: &[u8], offset: usize) -> Result<u32, ImageError> {
+    let bytes = data.get(offset..offset + 4).ok_or(ImageError::Truncated)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn new_buffer(
+    width: usize,
+    height: usize,
+    pixel_layout: PixelLayout,
+) -> Result<MemoryBuffer, ImageError> {
+    if width == 0 || height == 0 {
+        return Err(ImageError::InvalidHeader);
+    }
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(ImageError::TooLarge);
+    }
+    Ok(MemoryBuffer::new(width, height, pixel_layout))
+}
+
+const BMP_FILE_HEADER_SIZE: usize = 14;
+/// `BITMAPINFOHEADER`. The V4 and V5 headers extend it.
+const BMP_INFO_HEADER_SIZE: u32 = 40;
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+
+/// Returns the width and height of a BMP, and whether its rows are stored top-down.
+fn bmp_size(data: &[u8]) -> Result<(usize, usize, bool), ImageError> {
+    let header_size = read_u32_le(data, BMP_FILE_HEADER_SIZE)?;
+    if header_size < BMP_INFO_HEADER_SIZE {
+        // OS/2 `BITMAPCOREHEADER`
+        return Err(ImageError::Unsupported);
+    }
+    let width = read_u32_le(data, 18)? as i32;
+    let height = read_u32_le(data, 22)? as i32;
+    if width <= 0 || height == 0 {
+        return Err(ImageError::InvalidHeader);
+    }
+    // Rows are stored bottom-up unless the height is negative.
+    Ok((width as usize, height.unsigned_abs() as usize, height < 0))
+}
+
+/// Decodes an uncompressed 24-bit or 32-bit BMP.
+pub fn decode_bmp(data: &[u8], pixel_layout: PixelLayout) -> Result<MemoryBuffer, ImageError> {
+    if !data.starts_with(b"BM") {
+        return Err(ImageError::UnknownFormat);
+    }
+    let pixels_offset = read_u32_le(data, 10)? as usize;
+    let (width, height, top_down) = bmp_size(data)?;
+    let bits_per_pixel = read_u16_le(data, 28)?;
+    let compression = read_u32_le(data, 30)?;
+
+    let source_layout = match (bits_per_pixel, compression) {
+        (24, BI_RGB) | (32, BI_RGB) => PixelLayout::BGR,
+        (32, BI_BITFIELDS) => {
+            let masks = BMP_FILE_HEADER_SIZE + BMP_INFO_HEADER_SIZE as usize;
+            PixelLayout::from_masks(
+                read_u32_le(data, masks)?,
+                read_u32_le(data, masks + 4)?,
+                read_u32_le(data, masks + 8)?,
+            )
+        }
+        _ => return Err(ImageError::Unsupported),
+    };
+    let bytes_per_pixel = bits_per_pixel as usize / 8;
+    let stride = (width * bytes_per_pixel).next_multiple_of(4);
+
+    let mut buffer = new_buffer(width, height, pixel_layout)?;
+    let pixels = data.get(pixels_offset..).ok_or(ImageError::Truncated)?;
+    for y in 0..height {
+        let src_y = if top_down { y } else { height - 1 - y };
+        let src = pixels
+            .get(src_y * stride..src_y * stride + width * bytes_per_pixel)
+            .ok_or(ImageError::Truncated)?;
+        for (dst, src) in buffer
+            .row_mut(y)
+            .iter_mut()
+            .zip(src.chunks_exact(bytes_per_pixel))
+        {
+            let mut pixel = [0; 4];
+            pixel[..bytes_per_pixel].copy_from_slice(src);
+            let color = source_layout.decode(u32::from_le_bytes(pixel));
+            *dst = pixel_layout.encode(&color);
+        }
+    }
+    Ok(buffer)
+}
+
+const QOI_HEADER_SIZE: usize = 14;
+const QOI_OP_RGB: u8 = 0xfe;
+const QOI_OP_RGBA: u8 = 0xff;
+const QOI_OP_INDEX: u8 = 0x00;
+const QOI_OP_DIFF: u8 = 0x40;
+const QOI_OP_LUMA: u8 = 0x80;
+const QOI_MASK_2: u8 = 0xc0;
+
+fn qoi_size(data: &[u8]) -> Result<(usize, usize), ImageError> {
+    let header = data.get(..QOI_HEADER_SIZE).ok_or(ImageError::Truncated)?;
+    let width = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(header[8..12].try_into().unwrap()) as usize;
+    if !matches!(header[12], 3 | 4) {
+        return Err(ImageError::InvalidHeader);
+    }
+    Ok((width, height))
+}
+
+/// Decodes a QOI image. See <https://qoiformat.org/qoi-specification.pdf>.
+pub fn decode_qoi(data: &[u8], pixel_layout: PixelLayout) -> Result<MemoryBuffer, ImageError> {
+    if !data.starts_with(b"qoif") {
+        return Err(ImageError::UnknownFormat);
+    }
+    let (width, height) = qoi_size(data)?;
+    let mut buffer = new_buffer(width, height, pixel_layout)?;
+
+    let mut chunks = data[QOI_HEADER_SIZE..].iter().copied();
+    let mut next = || chunks.next().ok_or(ImageError::Truncated);
+    let mut index = [[0u8; 4]; 64];
+    let mut px = [0, 0, 0, 255u8];
+    let mut run = 0;
+    for y in 0..height {
+        for dst in buffer.row_mut(y) {
+            if run > 0 {
+                run -= 1;
+            } else {
+                let op = next()?;
+                match op {
+                    QOI_OP_RGB => {
+                        px = [next()?, next()?, next()?, px[3]];
+                    }
+                    QOI_OP_RGBA => {
+                        px = [next()?, next()?, next()?, next()?];
+                    }
+                    _ => match op & QOI_MASK_2 {
+                        QOI_OP_INDEX => px = index[op as usize],
+                        QOI_OP_DIFF => {
+                            px[0] = px[0].wrapping_add((op >> 4) & 0x03).wrapping_sub(2);
+                            px[1] = px[1].wrapping_add((op >> 2) & 0x03).wrapping_sub(2);
+                            px[2] = px[2].wrapping_add(op & 0x03).wrapping_sub(2);
+                        }
+                        QOI_OP_LUMA => {
+                            let dg = (op & 0x3f).wrapping_sub(32);
+                            let byte = next()?;
+                            let dr = dg.wrapping_add(byte >> 4).wrapping_sub(8);
+                            let db = dg.wrapping_add(byte & 0x0f).wrapping_sub(8);
+                            px[0] = px[0].wrapping_add(dr);
+                            px[1] = px[1].wrapping_add(dg);
+                            px[2] = px[2].wrapping_add(db);
+                        }
+                        // QOI_OP_RUN
+                        _ => run = (op & 0x3f) as usize,
+                    },
+                }
+                let [r, g, b, a] = px.map(|c| c as usize);
+                index[(r * 3 + g * 5 + b * 7 + a * 11) % 64] = px;
+            }
+            *dst = pixel_layout.encode(&PixelColor::new(px[0], px[1], px[2]));
+        }
+    }
+    Ok(buffer)
+}
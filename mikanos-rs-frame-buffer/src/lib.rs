@@ -1,8 +1,33 @@
 #![no_std]
 
+extern crate alloc;
+
+mod cursor;
+#[cfg(feature = "uefi")]
+mod gop;
+mod image;
+mod memory;
+mod ppm;
+mod psf;
+
 use core::slice;
-use uefi::proto::console::gop::{GraphicsOutput, PixelFormat};
 
+pub use cursor::draw_cursor;
+#[cfg(feature = "uefi")]
+pub use gop::{FrameBuffer, FrameBufferInfo};
+pub use image::{ImageError, decode_bmp, decode_image, decode_qoi, image_size};
+pub use memory::MemoryBuffer;
+pub use ppm::{PpmError, write_ppm};
+pub use psf::{Psf2Font, PsfError};
+
+/// The built-in 8x16 console font, rendered from DejaVu Sans Mono. See `fonts/README.md`.
+pub const DEFAULT_FONT: &[u8] = include_bytes!("../fonts/default-8x16.psf");
+
+/// Placement of a glyph bitmap, as returned by fontdue.
+///
+/// `xmin` is the offset of the bitmap's left edge from the pen position and `ymin` the offset of
+/// its bottom edge above the baseline.
+#[derive(Clone, Copy, Debug)]
 pub struct FontMetrics {
     xmin: i32,
     ymin: i32,
@@ -21,143 +46,548 @@ impl FontMetrics {
     }
 }
 
-pub struct Font {
+/// A glyph: its placement and a coverage bitmap of `width * height` bytes borrowed from a cache
+/// or rasterizer.
+pub struct Font<'a> {
     metrics: FontMetrics,
-    bitmap_ptr: *const u8,
+    bitmap: &'a [u8],
 }
 
-impl Font {
-    pub fn new(metrics: FontMetrics, bitmap_ptr: *const u8) -> Self {
-        Self {
-            metrics,
-            bitmap_ptr,
-        }
+impl<'a> Font<'a> {
+    /// Panics if `bitmap` is smaller than `metrics` says.
+    pub fn new(metrics: FontMetrics, bitmap: &'a [u8]) -> Self {
+        assert!(bitmap.len() >= metrics.width * metrics.height);
+        Self { metrics, bitmap }
     }
 
-    pub fn get_bitmap(&self) -> &[u8] {
-        let size = self.metrics.width * self.metrics.height;
-        unsafe { core::slice::from_raw_parts(self.bitmap_ptr, size) }
+    pub fn get_bitmap(&self) -> &'a [u8] {
+        &self.bitmap[..self.metrics.width * self.metrics.height]
     }
 }
 
+/// An RGBA color with straight (non-premultiplied) alpha.
+///
+/// Writes store the color channels only. Blending uses alpha to mix the color with what is
+/// already in the buffer, which is always opaque.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct PixelColor {
     r: u8,
     g: u8,
     b: u8,
+    a: u8,
+}
+
+/// How `blend_pixel` and `blend_rect` combine a color with the buffer contents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Draws the color over the buffer, weighted by its alpha.
+    SourceOver,
+    /// Adds the color, weighted by its alpha, to the buffer, saturating at white.
+    Additive,
+}
+
+/// Computes `a * b / 255` rounded to nearest.
+fn mul_div_255(a: u8, b: u8) -> u8 {
+    let x = a as u32 * b as u32 + 128;
+    ((x + (x >> 8)) >> 8) as u8
 }
 
 impl PixelColor {
-    pub fn new(r: u8, g: u8, b: u8) -> Self {
-        Self { r, g, b }
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self::rgba(r, g, b, 255)
     }
-}
 
-pub trait FrameBufferWriter {
-    fn get_buffer_mut(&self) -> *mut u8;
-    fn size(&self) -> usize;
-    fn get_pixels_per_scan_line(&self) -> usize;
-    fn get_horizontal_resolution(&self) -> usize;
-    fn get_vertical_resolution(&self) -> usize;
-    fn get_pixel_format(&self) -> PixelFormat;
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
 
-    // Default impls
-    fn as_slice_mut(&self) -> &'static mut [u8] {
-        unsafe { slice::from_raw_parts_mut(self.get_buffer_mut(), self.size()) }
-    }
-
-    fn write_pixel(&self, pos_x: usize, pos_y: usize, c: &PixelColor) {
-        let pixel_idx = self.get_pixels_per_scan_line() * pos_y + pos_x;
-        let pixel_format = self.get_pixel_format();
-        let p = self.as_slice_mut();
-        match pixel_format {
-            PixelFormat::Rgb => {
-                p[4 * pixel_idx] = c.r;
-                p[4 * pixel_idx + 1] = c.g;
-                p[4 * pixel_idx + 2] = c.b;
+    pub const fn alpha(&self) -> u8 {
+        self.a
+    }
+
+    pub const fn with_alpha(self, a: u8) -> Self {
+        Self { a, ..self }
+    }
+
+    /// Returns the color of `self` drawn onto `dst` with `mode`.
+    pub fn blend(&self, dst: &PixelColor, mode: BlendMode) -> PixelColor {
+        let a = self.a;
+        match mode {
+            BlendMode::SourceOver => {
+                let mix = |src: u8, dst: u8| mul_div_255(src, a) + mul_div_255(dst, 255 - a);
+                PixelColor::rgba(
+                    mix(self.r, dst.r),
+                    mix(self.g, dst.g),
+                    mix(self.b, dst.b),
+                    a + mul_div_255(dst.a, 255 - a),
+                )
             }
-            PixelFormat::Bgr => {
-                p[4 * pixel_idx] = c.b;
-                p[4 * pixel_idx + 1] = c.g;
-                p[4 * pixel_idx + 2] = c.r;
+            BlendMode::Additive => {
+                let add = |src: u8, dst: u8| dst.saturating_add(mul_div_255(src, a));
+                PixelColor::rgba(
+                    add(self.r, dst.r),
+                    add(self.g, dst.g),
+                    add(self.b, dst.b),
+                    dst.a.saturating_add(a),
+                )
             }
-            _ => unimplemented!(),
         }
     }
+}
 
-    fn write_char(&self, x: usize, y: usize, f: &Font, color: &PixelColor) {
-        let threshold = 80;
-        let bitmap = f.get_bitmap();
-        for i in 0..f.metrics.height {
-            for j in 0..f.metrics.width {
-                let idx = i * f.metrics.width + j;
-                if bitmap[idx] < threshold {
-                    continue;
-                }
-                let py =
-                    (y as i32 + 16 - f.metrics.height as i32 - f.metrics.ymin + i as i32) as usize;
-                let px = (x as i32 + j as i32) as usize;
-                if py < self.get_vertical_resolution() && px < self.get_horizontal_resolution() {
-                    self.write_pixel(px, py, color);
-                }
-            }
+/// Position and width of a color channel within a 32-bit pixel.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Channel {
+    shift: u8,
+    bits: u8,
+}
+
+impl Channel {
+    const fn byte(index: u8) -> Self {
+        Self {
+            shift: 8 * index,
+            bits: 8,
         }
     }
 
-    fn fill(&self, color: &PixelColor) {
-        for x in 0..self.get_horizontal_resolution() {
-            for y in 0..self.get_vertical_resolution() {
-                self.write_pixel(x, y, color)
-            }
+    fn from_mask(mask: u32) -> Self {
+        if mask == 0 {
+            return Self { shift: 0, bits: 0 };
+        }
+        Self {
+            shift: mask.trailing_zeros() as u8,
+            bits: mask.count_ones() as u8,
+        }
+    }
+
+    fn encode(&self, value: u8) -> u32 {
+        let value = if self.bits <= 8 {
+            value as u32 >> (8 - self.bits)
+        } else {
+            (value as u32) << (self.bits - 8)
+        };
+        value << self.shift
+    }
+
+    fn decode(&self, pixel: u32) -> u8 {
+        if self.bits == 0 {
+            return 0;
+        }
+        let value = (pixel >> self.shift) & (u32::MAX >> (32 - self.bits));
+        if self.bits <= 8 {
+            // Replicate the high bits so that the maximum value maps to 255.
+            let value = value << (8 - self.bits);
+            (value | (value >> self.bits)) as u8
+        } else {
+            (value >> (self.bits - 8)) as u8
         }
     }
 }
 
+/// How to encode a color into a 32-bit pixel, precomputed from the GOP pixel format.
+///
+/// With the `uefi` feature, `PixelLayout::new` builds one from a GOP mode.
 #[repr(C)]
-pub struct FrameBuffer {
-    frame_buffer: *mut u8,
-    pixels_per_scanline: usize,
-    horizontal_resolution: usize,
-    vertical_resolution: usize,
-    pixel_format: PixelFormat,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelLayout {
+    red: Channel,
+    green: Channel,
+    blue: Channel,
 }
 
-impl FrameBuffer {
-    pub fn new(gop: &mut GraphicsOutput) -> Self {
-        let (horizontal, vertical) = gop.current_mode_info().resolution();
-        let pixel_format = gop.current_mode_info().pixel_format();
+impl PixelLayout {
+    pub const RGB: Self = Self {
+        red: Channel::byte(0),
+        green: Channel::byte(1),
+        blue: Channel::byte(2),
+    };
+    pub const BGR: Self = Self {
+        red: Channel::byte(2),
+        green: Channel::byte(1),
+        blue: Channel::byte(0),
+    };
 
+    /// Builds a layout from the bits each channel occupies, e.g. `0x00ff0000` for red in BGR.
+    pub fn from_masks(red: u32, green: u32, blue: u32) -> Self {
         Self {
-            frame_buffer: gop.frame_buffer().as_mut_ptr(),
-            pixels_per_scanline: gop.current_mode_info().stride(),
-            horizontal_resolution: horizontal,
-            vertical_resolution: vertical,
-            pixel_format,
+            red: Channel::from_mask(red),
+            green: Channel::from_mask(green),
+            blue: Channel::from_mask(blue),
         }
     }
+
+    pub fn encode(&self, c: &PixelColor) -> u32 {
+        self.red.encode(c.r) | self.green.encode(c.g) | self.blue.encode(c.b)
+    }
+
+    pub fn decode(&self, pixel: u32) -> PixelColor {
+        PixelColor::new(
+            self.red.decode(pixel),
+            self.green.decode(pixel),
+            self.blue.decode(pixel),
+        )
+    }
 }
 
-impl FrameBufferWriter for FrameBuffer {
-    fn get_buffer_mut(&self) -> *mut u8 {
-        self.frame_buffer
+/// A rectangle in pixels. The position may be negative or off screen; drawing clips it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: isize,
+    pub y: isize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub const fn new(x: isize, y: isize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Saturates at `isize::MAX`, which is off screen anyway.
+    pub const fn right(&self) -> isize {
+        self.x.saturating_add_unsigned(self.width)
+    }
+
+    /// Saturates at `isize::MAX`, which is off screen anyway.
+    pub const fn bottom(&self) -> isize {
+        self.y.saturating_add_unsigned(self.height)
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Returns the overlapping part of both rectangles, or `None` if they don't overlap.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        if x >= right || y >= bottom {
+            return None;
+        }
+        Some(Rect::new(x, y, (right - x) as usize, (bottom - y) as usize))
     }
+}
+
+pub trait FrameBufferWriter {
+    /// The buffer as 32-bit pixels, `get_pixels_per_scan_line()` of them per row.
+    fn as_pixels(&self) -> &[u32];
+    fn as_pixels_mut(&mut self) -> &mut [u32];
+    fn get_pixels_per_scan_line(&self) -> usize;
+    fn get_horizontal_resolution(&self) -> usize;
+    fn get_vertical_resolution(&self) -> usize;
+    fn get_pixel_layout(&self) -> PixelLayout;
 
+    // Default impls
+    /// Size of the buffer in bytes.
     fn size(&self) -> usize {
-        4 * self.pixels_per_scanline * self.vertical_resolution
+        4 * self.as_pixels().len()
+    }
+
+    fn as_slice_mut(&mut self) -> &mut [u8] {
+        let pixels = self.as_pixels_mut();
+        // Any bit pattern is a valid `u8`, and the returned slice borrows `pixels`.
+        unsafe { slice::from_raw_parts_mut(pixels.as_mut_ptr() as *mut u8, 4 * pixels.len()) }
+    }
+
+    /// The visible pixels of row `y`.
+    fn row(&self, y: usize) -> &[u32] {
+        let start = self.get_pixels_per_scan_line() * y;
+        &self.as_pixels()[start..start + self.get_horizontal_resolution()]
+    }
+
+    fn row_mut(&mut self, y: usize) -> &mut [u32] {
+        let start = self.get_pixels_per_scan_line() * y;
+        let end = start + self.get_horizontal_resolution();
+        &mut self.as_pixels_mut()[start..end]
+    }
+
+    fn write_pixel(&mut self, pos_x: usize, pos_y: usize, c: &PixelColor) {
+        self.row_mut(pos_y)[pos_x] = self.get_pixel_layout().encode(c);
+    }
+
+    fn read_pixel(&self, pos_x: usize, pos_y: usize) -> PixelColor {
+        self.get_pixel_layout().decode(self.row(pos_y)[pos_x])
+    }
+
+    /// Draws a glyph with its pen position at `x` on the baseline `baseline_y`.
+    ///
+    /// The glyph's coverage weights the blend between `fg` and `bg`, or between `fg` and the
+    /// buffer contents if `bg` is `None`.
+    fn write_char(
+        &mut self,
+        x: usize,
+        baseline_y: usize,
+        f: &Font,
+        fg: &PixelColor,
+        bg: Option<&PixelColor>,
+    ) {
+        let bitmap = f.get_bitmap();
+        let layout = self.get_pixel_layout();
+        let glyph = Rect::new(
+            x as isize + f.metrics.xmin as isize,
+            baseline_y as isize - f.metrics.ymin as isize - f.metrics.height as isize,
+            f.metrics.width,
+            f.metrics.height,
+        );
+        let Some(visible) = glyph.intersection(&self.bounds()) else {
+            return;
+        };
+        for py in visible.y..visible.bottom() {
+            let row = self.row_mut(py as usize);
+            let i = (py - glyph.y) as usize;
+            for px in visible.x..visible.right() {
+                let j = (px - glyph.x) as usize;
+                let alpha = mul_div_255(fg.a, bitmap[i * f.metrics.width + j]);
+                let pixel = &mut row[px as usize];
+                let dst = match bg {
+                    Some(bg) => *bg,
+                    None if alpha == 0 => continue,
+                    None => layout.decode(*pixel),
+                };
+                *pixel = layout.encode(&fg.with_alpha(alpha).blend(&dst, BlendMode::SourceOver));
+            }
+        }
+    }
+
+    /// The whole visible area.
+    fn bounds(&self) -> Rect {
+        Rect::new(
+            0,
+            0,
+            self.get_horizontal_resolution(),
+            self.get_vertical_resolution(),
+        )
+    }
+
+    fn fill(&mut self, color: &PixelColor) {
+        self.fill_rect(self.bounds(), color);
+    }
+
+    fn fill_rect(&mut self, rect: Rect, color: &PixelColor) {
+        let Some(rect) = rect.intersection(&self.bounds()) else {
+            return;
+        };
+        let pixel = self.get_pixel_layout().encode(color);
+        let (x, width) = (rect.x as usize, rect.width);
+        for y in rect.y..rect.bottom() {
+            self.row_mut(y as usize)[x..x + width].fill(pixel);
+        }
     }
 
-    fn get_pixel_format(&self) -> PixelFormat {
-        self.pixel_format
+    /// Blends `color` into one pixel. Like `write_pixel`, the position must be in the buffer.
+    fn blend_pixel(&mut self, pos_x: usize, pos_y: usize, color: &PixelColor, mode: BlendMode) {
+        let layout = self.get_pixel_layout();
+        let pixel = &mut self.row_mut(pos_y)[pos_x];
+        *pixel = layout.encode(&color.blend(&layout.decode(*pixel), mode));
     }
 
-    fn get_pixels_per_scan_line(&self) -> usize {
-        self.pixels_per_scanline
+    fn blend_rect(&mut self, rect: Rect, color: &PixelColor, mode: BlendMode) {
+        match (color.a, mode) {
+            (0, _) => return,
+            (255, BlendMode::SourceOver) => return self.fill_rect(rect, color),
+            _ => {}
+        }
+        let Some(rect) = rect.intersection(&self.bounds()) else {
+            return;
+        };
+        let layout = self.get_pixel_layout();
+        let (x, width) = (rect.x as usize, rect.width);
+        for y in rect.y..rect.bottom() {
+            for pixel in &mut self.row_mut(y as usize)[x..x + width] {
+                *pixel = layout.encode(&color.blend(&layout.decode(*pixel), mode));
+            }
+        }
+    }
+
+    /// Draws the one pixel wide outline of `rect`.
+    fn draw_rect(&mut self, rect: Rect, color: &PixelColor) {
+        if rect.is_empty() {
+            return;
+        }
+        self.draw_hline(rect.x, rect.y, rect.width, color);
+        self.draw_hline(rect.x, rect.bottom() - 1, rect.width, color);
+        if rect.height > 2 {
+            self.draw_vline(rect.x, rect.y + 1, rect.height - 2, color);
+            self.draw_vline(rect.right() - 1, rect.y + 1, rect.height - 2, color);
+        }
     }
 
-    fn get_horizontal_resolution(&self) -> usize {
-        self.horizontal_resolution
+    /// Draws `len` pixels from `(x, y)` rightwards.
+    fn draw_hline(&mut self, x: isize, y: isize, len: usize, color: &PixelColor) {
+        self.fill_rect(Rect::new(x, y, len, 1), color);
     }
 
-    fn get_vertical_resolution(&self) -> usize {
-        self.vertical_resolution
+    /// Draws `len` pixels from `(x, y)` downwards.
+    fn draw_vline(&mut self, x: isize, y: isize, len: usize, color: &PixelColor) {
+        self.fill_rect(Rect::new(x, y, 1, len), color);
     }
+
+    /// Draws a line including both end points, with the pixels Bresenham's algorithm picks.
+    ///
+    /// The line is clipped to `bounds()` along its major axis first, so the work depends on the
+    /// visible part of the line rather than on its length.
+    fn draw_line(&mut self, x0: isize, y0: isize, x1: isize, y1: isize, color: &PixelColor) {
+        if y0 == y1 {
+            let len = x0.abs_diff(x1).saturating_add(1);
+            self.draw_hline(x0.min(x1), y0, len, color);
+            return;
+        }
+        if x0 == x1 {
+            let len = y0.abs_diff(y1).saturating_add(1);
+            self.draw_vline(x0, y0.min(y1), len, color);
+            return;
+        }
+        // Walk along the axis `a` with the larger extent. The `i`-th pixel is `i * minor / major`
+        // pixels off along the other axis `b`, rounded half up, which is what Bresenham's error
+        // term works out to. i128 holds any difference of two isize coordinates.
+        let swap = y0.abs_diff(y1) > x0.abs_diff(x1);
+        let (a0, b0, a1, b1) = if swap {
+            (y0, x0, y1, x1)
+        } else {
+            (x0, y0, x1, y1)
+        };
+        let (a_end, b_end) = if swap {
+            (
+                self.get_vertical_resolution(),
+                self.get_horizontal_resolution(),
+            )
+        } else {
+            (
+                self.get_horizontal_resolution(),
+                self.get_vertical_resolution(),
+            )
+        };
+        let (major, minor) = (a0.abs_diff(a1) as u128, b0.abs_diff(b1) as u128);
+        let step_a: i128 = if a0 < a1 { 1 } else { -1 };
+        let step_b: i128 = if b0 < b1 { 1 } else { -1 };
+        let (a0, b0) = (a0 as i128, b0 as i128);
+        // The steps `i` in `0..=major` for which `0 <= a0 + step_a * i < a_end`.
+        let (first, last) = if step_a > 0 {
+            (-a0, a_end as i128 - 1 - a0)
+        } else {
+            (a0 - (a_end as i128 - 1), a0)
+        };
+        for i in first.max(0)..=last.min(major as i128) {
+            // `i * minor` fits in u128 since both are below 2^64.
+            let product = i as u128 * minor;
+            let offset = product / major + u128::from(2 * (product % major) >= major);
+            let a = a0 + step_a * i;
+            let b = b0 + step_b * offset as i128;
+            if (0..b_end as i128).contains(&b) {
+                let (x, y) = if swap { (b, a) } else { (a, b) };
+                self.write_pixel(x as usize, y as usize, color);
+            }
+        }
+    }
+
+    /// Draws the outline of a circle with the midpoint algorithm.
+    fn draw_circle(&mut self, center_x: isize, center_y: isize, radius: usize, color: &PixelColor) {
+        let bounds = self.bounds();
+        let mut plot = |x: isize, y: isize| {
+            if bounds.intersection(&Rect::new(x, y, 1, 1)).is_some() {
+                self.write_pixel(x as usize, y as usize, color);
+            }
+        };
+        let mut x = radius as isize;
+        let mut y = 0;
+        let mut err = 1 - x;
+        while x >= y {
+            for (px, py) in [(x, y), (y, x), (-y, x), (-x, y)] {
+                plot(center_x + px, center_y + py);
+                plot(center_x - px, center_y - py);
+            }
+            y += 1;
+            if err < 0 {
+                err += 2 * y + 1;
+            } else {
+                x -= 1;
+                err += 2 * (y - x) + 1;
+            }
+        }
+    }
+
+    /// Copies `src_rect` of `src` so that its top-left corner lands at `(dst_x, dst_y)`.
+    ///
+    /// Use `copy_within` to move pixels inside one buffer.
+    fn blit<W: FrameBufferWriter + ?Sized>(
+        &mut self,
+        src: &W,
+        src_rect: Rect,
+        dst_x: isize,
+        dst_y: isize,
+    ) {
+        let Some((src_x, src_y, dst_rect)) =
+            clip_copy(&src.bounds(), &self.bounds(), src_rect, dst_x, dst_y)
+        else {
+            return;
+        };
+        let (src_layout, dst_layout) = (src.get_pixel_layout(), self.get_pixel_layout());
+        let (x, width) = (dst_rect.x as usize, dst_rect.width);
+        for row in 0..dst_rect.height {
+            let src_row = &src.row(src_y + row)[src_x..src_x + width];
+            let dst_row = &mut self.row_mut(dst_rect.y as usize + row)[x..x + width];
+            if src_layout == dst_layout {
+                dst_row.copy_from_slice(src_row);
+            } else {
+                for (dst, src) in dst_row.iter_mut().zip(src_row) {
+                    *dst = dst_layout.encode(&src_layout.decode(*src));
+                }
+            }
+        }
+    }
+
+    /// Moves `src_rect` of this buffer so that its top-left corner lands at `(dst_x, dst_y)`. The
+    /// source and destination may overlap.
+    fn copy_within(&mut self, src_rect: Rect, dst_x: isize, dst_y: isize) {
+        let bounds = self.bounds();
+        let Some((src_x, src_y, dst_rect)) = clip_copy(&bounds, &bounds, src_rect, dst_x, dst_y)
+        else {
+            return;
+        };
+        let stride = self.get_pixels_per_scan_line();
+        let (x, width) = (dst_rect.x as usize, dst_rect.width);
+        let pixels = self.as_pixels_mut();
+        // Go bottom-up when moving down so that rows are read before they are overwritten.
+        for i in 0..dst_rect.height {
+            let row = if dst_rect.y as usize > src_y {
+                dst_rect.height - 1 - i
+            } else {
+                i
+            };
+            let src_start = stride * (src_y + row) + src_x;
+            let dst_start = stride * (dst_rect.y as usize + row) + x;
+            pixels.copy_within(src_start..src_start + width, dst_start);
+        }
+    }
+}
+
+/// Clips a copy of `src_rect` to `(dst_x, dst_y)` against both buffers. Returns the top-left
+/// corner of the source pixels to copy and where they go.
+fn clip_copy(
+    src_bounds: &Rect,
+    dst_bounds: &Rect,
+    src_rect: Rect,
+    dst_x: isize,
+    dst_y: isize,
+) -> Option<(usize, usize, Rect)> {
+    let clipped = src_rect.intersection(src_bounds)?;
+    let (offset_x, offset_y) = (dst_x - src_rect.x, dst_y - src_rect.y);
+    let dst_rect = Rect::new(
+        clipped.x + offset_x,
+        clipped.y + offset_y,
+        clipped.width,
+        clipped.height,
+    )
+    .intersection(dst_bounds)?;
+    let src_x = (dst_rect.x - offset_x) as usize;
+    let src_y = (dst_rect.y - offset_y) as usize;
+    Some((src_x, src_y, dst_rect))
 }
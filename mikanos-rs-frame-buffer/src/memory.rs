@@ -0,0 +1,80 @@
+use alloc::vec::Vec;
+
+use crate::ppm::{self, PpmError};
+use crate::{FrameBufferWriter, PixelColor, PixelLayout, write_ppm};
+
+/// A frame buffer in ordinary memory, e.g. a shadow buffer or a render target for host tests.
+pub struct MemoryBuffer {
+    pixels: Vec<u32>,
+    pixels_per_scanline: usize,
+    horizontal_resolution: usize,
+    vertical_resolution: usize,
+    pixel_layout: PixelLayout,
+}
+
+impl MemoryBuffer {
+    pub fn new(width: usize, height: usize, pixel_layout: PixelLayout) -> Self {
+        Self::with_stride(width, height, width, pixel_layout)
+    }
+
+    /// Creates a buffer with `pixels_per_scanline` pixels per row, so that it can be copied to a
+    /// frame buffer with the same stride in one go.
+    pub fn with_stride(
+        width: usize,
+        height: usize,
+        pixels_per_scanline: usize,
+        pixel_layout: PixelLayout,
+    ) -> Self {
+        assert!(width <= pixels_per_scanline);
+        Self {
+            pixels: alloc::vec![0; pixels_per_scanline * height],
+            pixels_per_scanline,
+            horizontal_resolution: width,
+            vertical_resolution: height,
+            pixel_layout,
+        }
+    }
+
+    /// Encodes the visible area as a binary PPM (P6) image.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut ppm = Vec::new();
+        write_ppm(self, |bytes| ppm.extend_from_slice(bytes));
+        ppm
+    }
+
+    /// Decodes a binary PPM (P6) image with 8-bit channels.
+    pub fn from_ppm(data: &[u8], pixel_layout: PixelLayout) -> Result<Self, PpmError> {
+        let (width, height, pixels) = ppm::parse(data)?;
+        let mut buffer = Self::new(width, height, pixel_layout);
+        for (dst, rgb) in buffer.pixels.iter_mut().zip(pixels.chunks_exact(3)) {
+            *dst = pixel_layout.encode(&PixelColor::new(rgb[0], rgb[1], rgb[2]));
+        }
+        Ok(buffer)
+    }
+}
+
+impl FrameBufferWriter for MemoryBuffer {
+    fn as_pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    fn as_pixels_mut(&mut self) -> &mut [u32] {
+        &mut self.pixels
+    }
+
+    fn get_pixels_per_scan_line(&self) -> usize {
+        self.pixels_per_scanline
+    }
+
+    fn get_horizontal_resolution(&self) -> usize {
+        self.horizontal_resolution
+    }
+
+    fn get_vertical_resolution(&self) -> usize {
+        self.vertical_resolution
+    }
+
+    fn get_pixel_layout(&self) -> PixelLayout {
+        self.pixel_layout
+    }
+}
@@ -0,0 +1,92 @@
+use core::fmt;
+
+use crate::FrameBufferWriter;
+
+/// Encodes the visible area of `buffer` as a binary PPM (P6) image, handing it to `out` one row at
+/// a time so that large screens don't need a copy of the whole image.
+pub fn write_ppm<W: FrameBufferWriter + ?Sized>(buffer: &W, mut out: impl FnMut(&[u8])) {
+    let (width, height) = (
+        buffer.get_horizontal_resolution(),
+        buffer.get_vertical_resolution(),
+    );
+    out(alloc::format!("P6\n{} {}\n255\n", width, height).as_bytes());
+    let layout = buffer.get_pixel_layout();
+    let mut rgb = alloc::vec::Vec::with_capacity(3 * width);
+    for y in 0..height {
+        rgb.clear();
+        for &pixel in buffer.row(y) {
+            let c = layout.decode(pixel);
+            rgb.extend([c.r, c.g, c.b]);
+        }
+        out(&rgb);
+    }
+}
+
+/// Returns the width, height and RGB bytes of a binary PPM (P6) image with 8-bit channels.
+pub(crate) fn parse(data: &[u8]) -> Result<(usize, usize, &[u8]), PpmError> {
+    let (magic, rest) = next_token(data).ok_or(PpmError::InvalidHeader)?;
+    if magic != b"P6" {
+        return Err(PpmError::UnsupportedFormat);
+    }
+    let (width, rest) = next_number(rest)?;
+    let (height, rest) = next_number(rest)?;
+    let (max_value, rest) = next_number(rest)?;
+    if max_value != 255 {
+        return Err(PpmError::UnsupportedFormat);
+    }
+    // Exactly one whitespace character separates the header from the pixels.
+    let pixels = rest.get(1..).ok_or(PpmError::Truncated)?;
+    let size = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(3))
+        .ok_or(PpmError::InvalidHeader)?;
+    let pixels = pixels.get(..size).ok_or(PpmError::Truncated)?;
+    Ok((width, height, pixels))
+}
+
+/// Skips whitespace and `#` comments, then splits off the next header token.
+fn next_token(mut data: &[u8]) -> Option<(&[u8], &[u8])> {
+    loop {
+        match data.first()? {
+            b if b.is_ascii_whitespace() => data = &data[1..],
+            b'#' => {
+                let end = data.iter().position(|&b| b == b'\n')?;
+                data = &data[end..];
+            }
+            _ => break,
+        }
+    }
+    let end = data
+        .iter()
+        .position(|b| b.is_ascii_whitespace())
+        .unwrap_or(data.len());
+    Some(data.split_at(end))
+}
+
+fn next_number(data: &[u8]) -> Result<(usize, &[u8]), PpmError> {
+    let (token, rest) = next_token(data).ok_or(PpmError::InvalidHeader)?;
+    let number = core::str::from_utf8(token)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or(PpmError::InvalidHeader)?;
+    Ok((number, rest))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PpmError {
+    InvalidHeader,
+    UnsupportedFormat,
+    Truncated,
+}
+
+impl fmt::Display for PpmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(f, "invalid PPM header"),
+            Self::UnsupportedFormat => {
+                write!(f, "only binary PPM with 8-bit channels is supported")
+            }
+            Self::Truncated => write!(f, "the image is truncated"),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+// ref: https://www.win.tue.nl/~aeb/linux/kbd/font-formats-1.html
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::FontMetrics;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xff;
+const PSF2_START_SEQUENCE: u8 = 0xfe;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PsfError {
+    InvalidMagic,
+    Truncated,
+    InvalidHeader,
+}
+
+impl fmt::Display for PsfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidMagic => write!(f, "not a PSF2 font"),
+            Self::Truncated => write!(f, "the font file is truncated"),
+            Self::InvalidHeader => write!(f, "invalid PSF2 header"),
+        }
+    }
+}
+
+/// A PSF2 bitmap font borrowed from its file contents.
+pub struct Psf2Font<'a> {
+    glyphs: &'a [u8],
+    /// Maps code points to glyphs. Without it, glyph `i` is code point `i`.
+    unicode_table: Option<&'a [u8]>,
+    num_glyphs: usize,
+    bytes_per_glyph: usize,
+    width: usize,
+    height: usize,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+impl<'a> Psf2Font<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, PsfError> {
+        if data.len() < 32 {
+            return Err(PsfError::Truncated);
+        }
+        if data[..4] != PSF2_MAGIC {
+            return Err(PsfError::InvalidMagic);
+        }
+        let header_size = read_u32(data, 8) as usize;
+        let flags = read_u32(data, 12);
+        let num_glyphs = read_u32(data, 16) as usize;
+        let bytes_per_glyph = read_u32(data, 20) as usize;
+        let height = read_u32(data, 24) as usize;
+        let width = read_u32(data, 28) as usize;
+        if header_size < 32 || width == 0 || bytes_per_glyph != width.div_ceil(8) * height {
+            return Err(PsfError::InvalidHeader);
+        }
+        let glyphs_end = num_glyphs
+            .checked_mul(bytes_per_glyph)
+            .and_then(|size| size.checked_add(header_size))
+            .ok_or(PsfError::InvalidHeader)?;
+        let glyphs = data
+            .get(header_size..glyphs_end)
+            .ok_or(PsfError::Truncated)?;
+        let unicode_table = (flags & PSF2_HAS_UNICODE_TABLE != 0).then(|| &data[glyphs_end..]);
+        Ok(Self {
+            glyphs,
+            unicode_table,
+            num_glyphs,
+            bytes_per_glyph,
+            width,
+            height,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Distance from the top of a glyph cell to the baseline.
+    ///
+    /// PSF doesn't record it, so the bottom quarter of the cell is assumed to hold descenders.
+    pub fn ascent(&self) -> usize {
+        self.height - self.height / 4
+    }
+
+    fn glyph_index(&self, c: char) -> Option<usize> {
+        let Some(table) = self.unicode_table else {
+            return Some(c as usize).filter(|&i| i < self.num_glyphs);
+        };
+        // Each glyph has a list of UTF-8 encoded code points, then optional multi-character
+        // sequences (which we skip), terminated by 0xff.
+        let mut buf = [0; 4];
+        let encoded = c.encode_utf8(&mut buf).as_bytes();
+        for (index, entry) in table.split(|&b| b == PSF2_SEPARATOR).enumerate() {
+            if index >= self.num_glyphs {
+                break;
+            }
+            let singles = entry.split(|&b| b == PSF2_START_SEQUENCE).next().unwrap();
+            if singles.windows(encoded.len()).any(|w| w == encoded) {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.glyph_index(c).is_some()
+    }
+
+    /// Expands the glyph for `c` into an 8-bit coverage bitmap for `FrameBufferWriter::write_char`.
+    pub fn rasterize(&self, c: char) -> Option<(FontMetrics, Vec<u8>)> {
+        let index = self.glyph_index(c)?;
+        let glyph = &self.glyphs[index * self.bytes_per_glyph..(index + 1) * self.bytes_per_glyph];
+        let bytes_per_row = self.width.div_ceil(8);
+        let mut coverage = Vec::with_capacity(self.width * self.height);
+        for row in glyph.chunks_exact(bytes_per_row) {
+            coverage.extend((0..self.width).map(|x| {
+                let bit = row[x / 8] & (0x80 >> (x % 8));
+                if bit != 0 { 255 } else { 0 }
+            }));
+        }
+        let descent = (self.height - self.ascent()) as i32;
+        let metrics = FontMetrics::new(0, -descent, self.width, self.height);
+        Some((metrics, coverage))
+    }
+}
@@ -0,0 +1,132 @@
+//! Renders scenes into a `MemoryBuffer` and compares them with the PPM images in `tests/golden`.
+//!
+//! After an intended rendering change, regenerate the images with
+//! `UPDATE_GOLDEN=1 cargo test -p mikanos-rs-frame-buffer --test golden` and review them.
+use std::path::PathBuf;
+
+use mikanos_rs_frame_buffer::{
+    BlendMode, DEFAULT_FONT, Font, FrameBufferWriter, MemoryBuffer, PixelColor, PixelLayout,
+    Psf2Font, Rect, draw_cursor,
+};
+
+const BLACK: PixelColor = PixelColor::new(0, 0, 0);
+const WHITE: PixelColor = PixelColor::new(255, 255, 255);
+
+fn check_golden(name: &str, actual: &MemoryBuffer) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.ppm", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual.to_ppm()).unwrap();
+        return;
+    }
+    let data = std::fs::read(&path).unwrap_or_else(|e| {
+        panic!(
+            "{}: {} (set UPDATE_GOLDEN=1 to create it)",
+            path.display(),
+            e
+        )
+    });
+    let expected = MemoryBuffer::from_ppm(&data, actual.get_pixel_layout()).unwrap();
+    assert_eq!(
+        (
+            expected.get_horizontal_resolution(),
+            expected.get_vertical_resolution()
+        ),
+        (
+            actual.get_horizontal_resolution(),
+            actual.get_vertical_resolution()
+        ),
+        "{}: size mismatch",
+        name
+    );
+    let mismatches: Vec<_> = (0..actual.get_vertical_resolution())
+        .flat_map(|y| (0..actual.get_horizontal_resolution()).map(move |x| (x, y)))
+        .filter(|&(x, y)| actual.read_pixel(x, y) != expected.read_pixel(x, y))
+        .collect();
+    if let Some(&(x, y)) = mismatches.first() {
+        let actual_path = std::env::temp_dir().join(format!("{}.actual.ppm", name));
+        std::fs::write(&actual_path, actual.to_ppm()).unwrap();
+        panic!(
+            "{}: {} pixels differ, first at ({}, {}): expected {:?}, got {:?}. Rendered image: {}",
+            name,
+            mismatches.len(),
+            x,
+            y,
+            expected.read_pixel(x, y),
+            actual.read_pixel(x, y),
+            actual_path.display()
+        );
+    }
+}
+
+fn draw_text(buffer: &mut MemoryBuffer, font: &Psf2Font, x: usize, y: usize, s: &str) {
+    for (i, c) in s.chars().enumerate() {
+        let (metrics, bitmap) = font.rasterize(c).unwrap();
+        let glyph = Font::new(metrics, &bitmap);
+        let pen_x = x + i * font.width();
+        buffer.write_char(pen_x, y + font.ascent(), &glyph, &BLACK, Some(&WHITE));
+    }
+}
+
+fn render_text(pixel_layout: PixelLayout) -> MemoryBuffer {
+    let font = Psf2Font::parse(DEFAULT_FONT).unwrap();
+    let mut buffer = MemoryBuffer::new(160, 48, pixel_layout);
+    buffer.fill(&PixelColor::new(200, 200, 200));
+    draw_text(&mut buffer, &font, 4, 0, "Hello, MikanOS!");
+    draw_text(&mut buffer, &font, 4, 16, "0123456789 {}[]");
+    // Clipped at the right and bottom edges.
+    draw_text(&mut buffer, &font, 124, 40, "gjpqy");
+    buffer
+}
+
+#[test]
+fn text() {
+    check_golden("text", &render_text(PixelLayout::BGR));
+}
+
+#[test]
+fn text_rgb_layout() {
+    check_golden("text", &render_text(PixelLayout::RGB));
+}
+
+#[test]
+fn rects() {
+    let mut buffer = MemoryBuffer::new(96, 64, PixelLayout::BGR);
+    buffer.fill(&WHITE);
+    buffer.fill_rect(Rect::new(8, 8, 32, 24), &PixelColor::new(255, 0, 0));
+    buffer.draw_rect(Rect::new(4, 4, 40, 32), &BLACK);
+    buffer.blend_rect(
+        Rect::new(24, 16, 32, 32),
+        &PixelColor::rgba(0, 0, 255, 128),
+        BlendMode::SourceOver,
+    );
+    buffer.blend_rect(
+        Rect::new(48, 8, 24, 24),
+        &PixelColor::rgba(0, 255, 0, 160),
+        BlendMode::Additive,
+    );
+    // Partly off screen on every side.
+    buffer.fill_rect(Rect::new(-8, 52, 24, 24), &PixelColor::new(0, 128, 0));
+    buffer.draw_rect(Rect::new(80, -4, 24, 72), &PixelColor::new(128, 0, 128));
+    buffer.draw_line(0, 63, 95, 40, &BLACK);
+    buffer.draw_circle(64, 44, 12, &PixelColor::new(255, 128, 0));
+    check_golden("rects", &buffer);
+}
+
+#[test]
+fn cursor() {
+    let mut buffer = MemoryBuffer::new(64, 40, PixelLayout::BGR);
+    buffer.fill(&PixelColor::new(0, 128, 128));
+    draw_cursor(&mut buffer, 4, 4);
+    // Clipped at the right and bottom edges.
+    draw_cursor(&mut buffer, 52, 24);
+    check_golden("cursor", &buffer);
+}
+
+#[test]
+fn ppm_round_trip() {
+    let buffer = render_text(PixelLayout::BGR);
+    let decoded = MemoryBuffer::from_ppm(&buffer.to_ppm(), PixelLayout::RGB).unwrap();
+    assert_eq!(decoded.to_ppm(), buffer.to_ppm());
+}
@@ -0,0 +1,91 @@
+//! Decodes the images in `tests/images`, which all show the same picture as `sample.ppm`.
+use mikanos_rs_frame_buffer::{
+    ImageError, MemoryBuffer, PixelLayout, decode_bmp, decode_image, decode_qoi, image_size,
+};
+
+fn sample(name: &str) -> Vec<u8> {
+    std::fs::read(format!(
+        "{}/tests/images/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+}
+
+fn expected() -> Vec<u8> {
+    let ppm = sample("sample.ppm");
+    MemoryBuffer::from_ppm(&ppm, PixelLayout::BGR)
+        .unwrap()
+        .to_ppm()
+}
+
+#[test]
+fn bmp_24_bit_bottom_up() {
+    let image = decode_bmp(&sample("sample-24.bmp"), PixelLayout::BGR).unwrap();
+    assert_eq!(image.to_ppm(), expected());
+}
+
+#[test]
+fn bmp_32_bit_top_down_bitfields() {
+    let image = decode_bmp(&sample("sample-32.bmp"), PixelLayout::RGB).unwrap();
+    assert_eq!(image.to_ppm(), expected());
+}
+
+#[test]
+fn qoi() {
+    let image = decode_qoi(&sample("sample.qoi"), PixelLayout::BGR).unwrap();
+    assert_eq!(image.to_ppm(), expected());
+}
+
+#[test]
+fn detects_format() {
+    for name in ["sample-24.bmp", "sample-32.bmp", "sample.qoi"] {
+        let image = decode_image(&sample(name), PixelLayout::BGR).unwrap();
+        assert_eq!(image.to_ppm(), expected(), "{}", name);
+    }
+    assert_eq!(
+        decode_image(&sample("sample.ppm"), PixelLayout::BGR).err(),
+        Some(ImageError::UnknownFormat)
+    );
+}
+
+#[test]
+fn reads_size_from_header() {
+    for name in ["sample-24.bmp", "sample-32.bmp", "sample.qoi"] {
+        assert_eq!(image_size(&sample(name)), Ok((23, 16)), "{}", name);
+    }
+    let mut huge = sample("sample.qoi");
+    huge[4..8].copy_from_slice(&100_000u32.to_be_bytes());
+    assert_eq!(image_size(&huge[..14]), Ok((100_000, 16)));
+    assert_eq!(
+        image_size(&sample("sample.ppm")),
+        Err(ImageError::UnknownFormat)
+    );
+}
+
+#[test]
+fn rejects_broken_images() {
+    let qoi = sample("sample.qoi");
+    assert_eq!(
+        decode_qoi(&qoi[..qoi.len() / 2], PixelLayout::BGR).err(),
+        Some(ImageError::Truncated)
+    );
+    let bmp = sample("sample-24.bmp");
+    assert_eq!(
+        decode_bmp(&bmp[..bmp.len() - 4], PixelLayout::BGR).err(),
+        Some(ImageError::Truncated)
+    );
+    // 8-bit palette images aren't supported.
+    let mut paletted = bmp.clone();
+    paletted[28] = 8;
+    assert_eq!(
+        decode_bmp(&paletted, PixelLayout::BGR).err(),
+        Some(ImageError::Unsupported)
+    );
+    let mut huge = qoi.clone();
+    huge[4..8].copy_from_slice(&100_000u32.to_be_bytes());
+    assert_eq!(
+        decode_qoi(&huge, PixelLayout::BGR).err(),
+        Some(ImageError::TooLarge)
+    );
+}
@@ -0,0 +1,91 @@
+//! Checks that `draw_line` draws the same pixels as an unclipped Bresenham walk, also for lines
+//! that reach far off screen.
+use mikanos_rs_frame_buffer::{FrameBufferWriter, MemoryBuffer, PixelColor, PixelLayout};
+
+const BLACK: PixelColor = PixelColor::new(0, 0, 0);
+const WHITE: PixelColor = PixelColor::new(255, 255, 255);
+const WIDTH: usize = 40;
+const HEIGHT: usize = 30;
+
+/// The on-screen pixels of the line from `(x0, y0)` to `(x1, y1)`, walked pixel by pixel.
+fn bresenham(x0: isize, y0: isize, x1: isize, y1: isize) -> Vec<(usize, usize)> {
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let step_x = if x0 < x1 { 1 } else { -1 };
+    let step_y = if y0 < y1 { 1 } else { -1 };
+    let (mut x, mut y) = (x0, y0);
+    let mut err = dx + dy;
+    let mut pixels = Vec::new();
+    loop {
+        if (0..WIDTH as isize).contains(&x) && (0..HEIGHT as isize).contains(&y) {
+            pixels.push((x as usize, y as usize));
+        }
+        if x == x1 && y == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += step_x;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += step_y;
+        }
+    }
+    pixels.sort();
+    pixels
+}
+
+fn draw(x0: isize, y0: isize, x1: isize, y1: isize) -> Vec<(usize, usize)> {
+    let mut buffer = MemoryBuffer::new(WIDTH, HEIGHT, PixelLayout::BGR);
+    buffer.fill(&WHITE);
+    buffer.draw_line(x0, y0, x1, y1, &BLACK);
+    (0..WIDTH)
+        .flat_map(|x| (0..HEIGHT).map(move |y| (x, y)))
+        .filter(|&(x, y)| buffer.read_pixel(x, y) == BLACK)
+        .collect()
+}
+
+#[test]
+fn matches_bresenham() {
+    let points = [-50, -7, -1, 0, 3, 17, 29, 39, 40, 41, 90];
+    for &x0 in &points {
+        for &y0 in &points {
+            for (x1, y1) in [(13, 11), (-20, 35), (60, -9), (x0 + 3, y0 - 25), (5, y0)] {
+                assert_eq!(
+                    draw(x0, y0, x1, y1),
+                    bresenham(x0, y0, x1, y1),
+                    "({}, {}) to ({}, {})",
+                    x0,
+                    y0,
+                    x1,
+                    y1
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn clips_huge_lines() {
+    // 5 rows over 2e9 columns. The screen is just past the middle, which rounds up to row 3.
+    assert_eq!(
+        draw(-1_000_000_000, 0, 1_000_000_000, 5),
+        (0..WIDTH).map(|x| (x, 3)).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        draw(isize::MIN, 3, isize::MAX, 3),
+        (0..WIDTH).map(|x| (x, 3)).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        draw(7, isize::MAX, 7, isize::MIN),
+        (0..HEIGHT).map(|y| (7, y)).collect::<Vec<_>>()
+    );
+    // Passes through (0, 0) exactly in the middle.
+    assert_eq!(
+        draw(isize::MIN + 1, isize::MIN + 1, isize::MAX, isize::MAX),
+        (0..HEIGHT).map(|i| (i, i)).collect::<Vec<_>>()
+    );
+    assert!(draw(isize::MIN, isize::MAX, isize::MAX, isize::MIN).is_empty());
+}
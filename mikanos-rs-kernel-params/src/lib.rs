@@ -0,0 +1,264 @@
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+/// Verbosity of the C++ USB driver's logger. The values match `LogLevel` in `logger.hpp`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(i32)]
+pub enum LogLevel {
+    Error = 3,
+    Warn = 4,
+    Info = 6,
+    Debug = 7,
+}
+
+/// Boot parameters given on the kernel command line.
+///
+/// The command line is a whitespace-separated list of `key=value` pairs, e.g.
+/// `loglevel=debug init=/bin/shell timer_hz=100`. Unknown or malformed entries are ignored.
+#[derive(Debug)]
+pub struct KernelParams {
+    pub log_level: LogLevel,
+    /// Path in the initrd of the first program to run.
+    pub init: Option<String>,
+    /// Local APIC timer frequency. Without it, the timer runs uncalibrated.
+    pub timer_hz: Option<u32>,
+    /// Whether to start the demo tasks B and C.
+    pub demo_tasks: bool,
+    /// Console font size in pixels.
+    pub font_size: f32,
+    /// Paths in the initrd of PSF2 or TrueType fonts to take the glyphs missing from the console
+    /// font from, in order.
+    pub fallback_fonts: Vec<String>,
+    /// Number of rows the console keeps after they scroll off the screen.
+    pub scrollback_lines: usize,
+    /// Path in the initrd of a BMP or QOI image to show behind the console.
+    pub wallpaper: Option<String>,
+}
+
+impl Default for KernelParams {
+    fn default() -> Self {
+        Self {
+            log_level: LogLevel::Info,
+            init: None,
+            timer_hz: None,
+            demo_tasks: true,
+            font_size: 16.0,
+            fallback_fonts: Vec::new(),
+            scrollback_lines: 1000,
+            wallpaper: None,
+        }
+    }
+}
+
+/// An entry of the command line that was ignored.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParamError<'a> {
+    /// The value of a known parameter can't be parsed, e.g. `timer_hz=fast`.
+    InvalidValue {
+        key: &'a str,
+        value: &'a str,
+    },
+    Unknown(&'a str),
+}
+
+impl fmt::Display for ParamError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidValue { key, value } => write!(f, "invalid {} {:?}", key, value),
+            Self::Unknown(arg) => write!(f, "ignoring unknown parameter {:?}", arg),
+        }
+    }
+}
+
+impl KernelParams {
+    /// Parses a command line. Unknown or malformed entries are passed to `report` and ignored.
+    pub fn parse<'a>(cmdline: &'a str, mut report: impl FnMut(ParamError<'a>)) -> Self {
+        let mut params = Self::default();
+        for arg in cmdline.split_ascii_whitespace() {
+            let (key, value) = match arg.split_once('=') {
+                Some((key, value)) => (key, Some(value)),
+                None => (arg, None),
+            };
+            let mut invalid = |value| report(ParamError::InvalidValue { key, value });
+            match (key, value) {
+                ("loglevel", Some(value)) => match parse_log_level(value) {
+                    Some(level) => params.log_level = level,
+                    None => invalid(value),
+                },
+                ("init", Some(value)) if !value.is_empty() => {
+                    params.init = Some(value.to_string());
+                }
+                ("timer_hz", Some(value)) => match value.parse() {
+                    Ok(hz) if hz > 0 => params.timer_hz = Some(hz),
+                    _ => invalid(value),
+                },
+                ("demo_tasks", Some(value)) => match parse_bool(value) {
+                    Some(enabled) => params.demo_tasks = enabled,
+                    None => invalid(value),
+                },
+                ("fontsize", Some(value)) => match value.parse::<f32>() {
+                    Ok(size) if (4.0..=128.0).contains(&size) => params.font_size = size,
+                    _ => invalid(value),
+                },
+                ("fallback_fonts", Some(value)) => {
+                    params.fallback_fonts = value
+                        .split(',')
+                        .filter(|path| !path.is_empty())
+                        .map(ToString::to_string)
+                        .collect();
+                }
+                ("scrollback", Some(value)) => match value.parse() {
+                    Ok(lines) => params.scrollback_lines = lines,
+                    Err(_) => invalid(value),
+                },
+                ("wallpaper", Some(value)) if !value.is_empty() => {
+                    params.wallpaper = Some(value.to_string());
+                }
+                _ => report(ParamError::Unknown(arg)),
+            }
+        }
+        params
+    }
+}
+
+fn parse_log_level(s: &str) -> Option<LogLevel> {
+    match s {
+        "error" => Some(LogLevel::Error),
+        "warn" => Some(LogLevel::Warn),
+        "info" => Some(LogLevel::Info),
+        "debug" => Some(LogLevel::Debug),
+        _ => None,
+    }
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s {
+        "1" | "on" | "yes" | "true" => Some(true),
+        "0" | "off" | "no" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `cmdline` and collects what was reported.
+    fn parse(cmdline: &str) -> (KernelParams, Vec<ParamError>) {
+        let mut errors = Vec::new();
+        let params = KernelParams::parse(cmdline, |err| errors.push(err));
+        (params, errors)
+    }
+
+    #[test]
+    fn empty_cmdline_gives_defaults() {
+        let (params, errors) = parse("  ");
+        assert!(errors.is_empty());
+        assert_eq!(params.log_level, LogLevel::Info);
+        assert_eq!(params.init, None);
+        assert_eq!(params.timer_hz, None);
+        assert!(params.demo_tasks);
+        assert_eq!(params.font_size, 16.0);
+        assert!(params.fallback_fonts.is_empty());
+        assert_eq!(params.scrollback_lines, 1000);
+        assert_eq!(params.wallpaper, None);
+    }
+
+    #[test]
+    fn parses_loglevel() {
+        for (value, level) in [
+            ("error", LogLevel::Error),
+            ("warn", LogLevel::Warn),
+            ("info", LogLevel::Info),
+            ("debug", LogLevel::Debug),
+        ] {
+            let cmdline = format!("loglevel={}", value);
+            let (params, errors) = parse(&cmdline);
+            assert!(errors.is_empty());
+            assert_eq!(params.log_level, level);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_loglevel() {
+        let (params, errors) = parse("loglevel=DEBUG loglevel=");
+        assert_eq!(errors, [
+            ParamError::InvalidValue {
+                key: "loglevel",
+                value: "DEBUG"
+            },
+            ParamError::InvalidValue {
+                key: "loglevel",
+                value: ""
+            },
+        ]);
+        assert_eq!(params.log_level, LogLevel::Info);
+    }
+
+    #[test]
+    fn parses_timer_hz() {
+        let (params, errors) = parse("timer_hz=100");
+        assert!(errors.is_empty());
+        assert_eq!(params.timer_hz, Some(100));
+    }
+
+    #[test]
+    fn rejects_invalid_timer_hz() {
+        let (params, errors) = parse("timer_hz=fast timer_hz=0 timer_hz=-1 timer_hz=5000000000");
+        assert_eq!(
+            errors,
+            ["fast", "0", "-1", "5000000000"].map(|value| ParamError::InvalidValue {
+                key: "timer_hz",
+                value
+            })
+        );
+        assert_eq!(params.timer_hz, None);
+    }
+
+    #[test]
+    fn parses_other_values() {
+        let cmdline = "init=/bin/shell demo_tasks=off fontsize=24 \
+                       fallback_fonts=/a.ttf,,/b.psf scrollback=0 wallpaper=/w.qoi";
+        let (params, errors) = parse(cmdline);
+        assert!(errors.is_empty());
+        assert_eq!(params.init.as_deref(), Some("/bin/shell"));
+        assert!(!params.demo_tasks);
+        assert_eq!(params.font_size, 24.0);
+        assert_eq!(params.fallback_fonts, ["/a.ttf", "/b.psf"]);
+        assert_eq!(params.scrollback_lines, 0);
+        assert_eq!(params.wallpaper.as_deref(), Some("/w.qoi"));
+    }
+
+    #[test]
+    fn later_duplicates_win() {
+        let (params, errors) = parse("loglevel=debug timer_hz=100 loglevel=warn timer_hz=250");
+        assert!(errors.is_empty());
+        assert_eq!(params.log_level, LogLevel::Warn);
+        assert_eq!(params.timer_hz, Some(250));
+    }
+
+    #[test]
+    fn invalid_duplicate_keeps_earlier_value() {
+        let (params, errors) = parse("timer_hz=100 timer_hz=x");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(params.timer_hz, Some(100));
+    }
+
+    #[test]
+    fn reports_unknown_parameters() {
+        let (params, errors) = parse("quiet nosmp init= foo=bar loglevel");
+        assert_eq!(
+            errors,
+            ["quiet", "nosmp", "init=", "foo=bar", "loglevel"].map(ParamError::Unknown)
+        );
+        assert_eq!(params.init, None);
+        assert_eq!(params.log_level, LogLevel::Info);
+    }
+}
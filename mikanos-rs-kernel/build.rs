@@ -13,9 +13,12 @@ fn main() {
             .unwrap();
     }
 
-    std::fs::create_dir_all("./fonts/").unwrap();
+    let ttf = std::env::var_os("CARGO_FEATURE_TTF").is_some();
+    if ttf {
+        std::fs::create_dir_all("./fonts/").unwrap();
+    }
 
-    if !std::fs::exists("./fonts/Tamzen7x14r.ttf").unwrap() {
+    if ttf && !std::fs::exists("./fonts/Tamzen7x14r.ttf").unwrap() {
         std::process::Command::new("wget")
             .args([
                 "https://raw.githubusercontent.com/sunaku/tamzen-font/3255e8259bc9b880c60ab8b737ec8aa574e00d75/ttf/Tamzen7x14r.ttf"
@@ -25,6 +28,16 @@ fn main() {
             .unwrap();
     }
 
+    // Optionally build a wallpaper image into the kernel. See `src/wallpaper.rs`.
+    println!("cargo::rustc-check-cfg=cfg(embedded_wallpaper)");
+    println!("cargo::rerun-if-env-changed=MIKANOS_WALLPAPER");
+    if let Some(path) = std::env::var_os("MIKANOS_WALLPAPER") {
+        let path = std::fs::canonicalize(path).expect("MIKANOS_WALLPAPER not found");
+        println!("cargo::rerun-if-changed={}", path.display());
+        println!("cargo::rustc-env=MIKANOS_WALLPAPER_PATH={}", path.display());
+        println!("cargo::rustc-cfg=embedded_wallpaper");
+    }
+
     let newlib_support_object = cc::Build::new()
         .flag("-Wno-unused-parameter")
         .flag("-ffreestanding")
@@ -58,10 +58,13 @@ void set_default_mouse_observer(MouseObserverType mouse_observer) {
         };
 }
 
-void set_default_keyboard_observer() {
+extern "C" typedef void (*KeyboardObserverType)(uint8_t modifier, uint8_t keycode, bool press);
+
+void set_default_keyboard_observer(KeyboardObserverType keyboard_observer) {
     usb::HIDKeyboardDriver::default_observer =
-        [](uint8_t modifier, uint8_t keycode, bool press) {
+        [keyboard_observer](uint8_t modifier, uint8_t keycode, bool press) {
             Log(kInfo, "Keyboard event: modifier=%d, keycode=%d, press=%d\n", modifier, keycode, press);
+            keyboard_observer(modifier, keycode, press);
         };
 }
 
@@ -8,6 +8,14 @@ namespace {
   LogLevel log_level = kInfo;
 }
 
+void SetLogLevel(LogLevel level) {
+    log_level = level;
+}
+
+extern "C" void mikanos_rs_set_log_level(LogLevel level) {
+    SetLogLevel(level);
+}
+
 int Log(LogLevel level, const char* format, ...) {
     if (level > log_level) {
         return 0;
@@ -0,0 +1,246 @@
+// ref: ACPI Specification 6.5, 5.2 ACPI System Description Tables
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+
+#[repr(C, packed)]
+struct Rsdp {
+    signature: [u8; 8],
+    checksum: u8,
+    oem_id: [u8; 6],
+    revision: u8,
+    rsdt_address: u32,
+    // The fields below exist since ACPI 2.0 (revision >= 2).
+    length: u32,
+    xsdt_address: u64,
+    extended_checksum: u8,
+    reserved: [u8; 3],
+}
+
+#[repr(C, packed)]
+pub struct DescriptionHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+}
+
+#[derive(Debug)]
+pub enum AcpiError {
+    NotProvided,
+    InvalidRsdpSignature,
+    InvalidRsdpChecksum,
+    InvalidXsdt,
+    InvalidRsdt,
+}
+
+impl fmt::Display for AcpiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotProvided => write!(f, "the loader did not find an RSDP"),
+            Self::InvalidRsdpSignature => write!(f, "invalid RSDP signature"),
+            Self::InvalidRsdpChecksum => write!(f, "invalid RSDP checksum"),
+            Self::InvalidXsdt => write!(f, "invalid XSDT"),
+            Self::InvalidRsdt => write!(f, "invalid RSDT"),
+        }
+    }
+}
+
+fn sum_bytes(addr: *const u8, len: usize) -> u8 {
+    let bytes = unsafe { core::slice::from_raw_parts(addr, len) };
+    bytes.iter().fold(0, |sum, &b| sum.wrapping_add(b))
+}
+
+impl Rsdp {
+    fn validate(&self) -> Result<(), AcpiError> {
+        if &self.signature != b"RSD PTR " {
+            return Err(AcpiError::InvalidRsdpSignature);
+        }
+        let addr = self as *const Self as *const u8;
+        if sum_bytes(addr, 20) != 0
+            || (self.has_xsdt() && sum_bytes(addr, self.length as usize) != 0)
+        {
+            return Err(AcpiError::InvalidRsdpChecksum);
+        }
+        Ok(())
+    }
+
+    /// Whether this is an ACPI 2.0+ RSDP. An ACPI 1.0 one (revision 0) only has the RSDT.
+    fn has_xsdt(&self) -> bool {
+        self.revision >= 2
+    }
+}
+
+impl DescriptionHeader {
+    fn is_valid(&self, signature: &[u8; 4]) -> bool {
+        &self.signature == signature
+            && sum_bytes(self as *const Self as *const u8, self.length as usize) == 0
+    }
+
+    pub fn signature(&self) -> &str {
+        core::str::from_utf8(&self.signature).unwrap_or("????")
+    }
+}
+
+/// Fixed ACPI Description Table, up to the fields we use. Tables of older revisions are shorter,
+/// so read them with `Fadt::from_table`.
+#[repr(C, packed)]
+struct Fadt {
+    header: DescriptionHeader,
+    firmware_ctrl: u32,
+    dsdt: u32,
+    reserved1: [u8; 76 - 44],
+    pm_tmr_blk: u32,
+    reserved2: [u8; 112 - 80],
+    flags: u32,
+    reserved3: [u8; 140 - 116],
+    // Since ACPI 2.0.
+    x_dsdt: u64,
+}
+
+const PM_TIMER_FREQ: u64 = 3579545;
+// Set in `Fadt::flags` when the PM timer counter is 32 bits wide rather than 24.
+const TMR_VAL_EXT: u32 = 1 << 8;
+// Port I/O takes far longer than 1/16 of a PM timer tick (about 17 ns), so a working timer advances
+// by `ticks` within this many reads per tick.
+const MAX_PM_TIMER_READS_PER_TICK: u64 = 16;
+
+impl Fadt {
+    /// Copies the FADT `table`. Fields beyond the end of a short table read as 0.
+    fn from_table(table: &DescriptionHeader) -> Self {
+        // All fields are integers, for which zero bytes are valid.
+        let mut fadt: Self = unsafe { core::mem::zeroed() };
+        let len = (table.length as usize).min(size_of::<Self>());
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                table as *const DescriptionHeader as *const u8,
+                &mut fadt as *mut Self as *mut u8,
+                len,
+            );
+        }
+        fadt
+    }
+
+    /// Returns the DSDT, which is referenced from the FADT rather than listed in the XSDT or RSDT.
+    fn dsdt(&self) -> Option<&'static DescriptionHeader> {
+        let addr = if self.x_dsdt != 0 {
+            self.x_dsdt
+        } else {
+            self.dsdt as u64
+        };
+        let dsdt = unsafe { (addr as *const DescriptionHeader).as_ref()? };
+        dsdt.is_valid(b"DSDT").then_some(dsdt)
+    }
+}
+
+/// Returns the tables listed in the XSDT (64-bit entries) or RSDT (32-bit entries), skipping ones
+/// with a broken checksum.
+fn root_entries(
+    root: &DescriptionHeader,
+    entry_size: usize,
+) -> impl Iterator<Item = &'static DescriptionHeader> {
+    let header = root as *const DescriptionHeader;
+    let count = (root.length as usize).saturating_sub(size_of::<DescriptionHeader>()) / entry_size;
+    let first = unsafe { header.add(1) as *const u8 };
+    (0..count)
+        .map(move |i| unsafe {
+            let entry = first.add(i * entry_size);
+            let addr = if entry_size == 8 {
+                (entry as *const u64).read_unaligned()
+            } else {
+                (entry as *const u32).read_unaligned() as u64
+            };
+            &*(addr as *const DescriptionHeader)
+        })
+        .filter(|table| table.is_valid(&table.signature))
+}
+
+/// Copies a table to the heap so that it survives reclaiming the firmware's ACPI memory.
+fn copy_table(table: &DescriptionHeader) -> &'static DescriptionHeader {
+    let bytes = unsafe {
+        core::slice::from_raw_parts(table as *const _ as *const u8, table.length as usize)
+    };
+    let copy = Box::leak(Box::<[u8]>::from(bytes));
+    unsafe { &*(copy.as_ptr() as *const DescriptionHeader) }
+}
+
+static TABLES: spin::Once<Vec<&'static DescriptionHeader>> = spin::Once::new();
+
+/// Validates the RSDP at physical address `rsdp` and copies the tables listed in the XSDT (or the
+/// RSDT for ACPI 1.0) and the DSDT to the heap. Afterwards, `MemoryType::ACPI_RECLAIM` regions can
+/// be reused.
+///
+/// # Safety
+///
+/// `rsdp` must be 0 or the address passed by the firmware, with ACPI tables identity-mapped.
+pub unsafe fn init(rsdp: u64) -> Result<(), AcpiError> {
+    if rsdp == 0 {
+        return Err(AcpiError::NotProvided);
+    }
+    let rsdp = unsafe { &*(rsdp as *const Rsdp) };
+    rsdp.validate()?;
+    let mut tables: Vec<_> = if rsdp.has_xsdt() {
+        let xsdt = unsafe { &*(rsdp.xsdt_address as *const DescriptionHeader) };
+        if !xsdt.is_valid(b"XSDT") {
+            return Err(AcpiError::InvalidXsdt);
+        }
+        root_entries(xsdt, 8).map(copy_table).collect()
+    } else {
+        let rsdt = unsafe { &*(rsdp.rsdt_address as u64 as *const DescriptionHeader) };
+        if !rsdt.is_valid(b"RSDT") {
+            return Err(AcpiError::InvalidRsdt);
+        }
+        root_entries(rsdt, 4).map(copy_table).collect()
+    };
+    let dsdt = tables
+        .iter()
+        .find(|table| &table.signature == b"FACP")
+        .and_then(|fadt| Fadt::from_table(fadt).dsdt());
+    if let Some(dsdt) = dsdt {
+        tables.push(copy_table(dsdt));
+    }
+    TABLES.call_once(|| tables);
+    Ok(())
+}
+
+/// Iterates over the copied tables.
+pub fn tables() -> impl Iterator<Item = &'static DescriptionHeader> {
+    TABLES.get().into_iter().flatten().copied()
+}
+
+/// Returns the table with `signature`, e.g. `b"FACP"` or `b"APIC"`.
+pub fn find_table(signature: &[u8; 4]) -> Option<&'static DescriptionHeader> {
+    tables().find(|table| &table.signature == signature)
+}
+
+/// Busy-waits for `msec` milliseconds using the ACPI PM timer. Returns false without waiting if
+/// there is no FADT or PM timer, or early if the timer does not advance.
+pub fn wait_milliseconds(msec: u64) -> bool {
+    let Some(fadt) = find_table(b"FACP") else {
+        return false;
+    };
+    let fadt = Fadt::from_table(fadt);
+    // Hardware-reduced ACPI platforms have no PM timer.
+    if fadt.pm_tmr_blk == 0 {
+        return false;
+    }
+    let mut port = x86_64::instructions::port::Port::<u32>::new(fadt.pm_tmr_blk as u16);
+    let mask: u64 = if fadt.flags & TMR_VAL_EXT != 0 {
+        0xffff_ffff
+    } else {
+        0xff_ffff
+    };
+    let start = unsafe { port.read() } as u64;
+    let ticks = PM_TIMER_FREQ * msec / 1000;
+    for _ in 0..=ticks * MAX_PM_TIMER_READS_PER_TICK {
+        if (unsafe { port.read() } as u64).wrapping_sub(start) & mask >= ticks {
+            return true;
+        }
+    }
+    false
+}
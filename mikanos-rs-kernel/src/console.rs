@@ -1,159 +1,645 @@
-use mikanos_rs_frame_buffer::{Font, FontMetrics, FrameBuffer, FrameBufferWriter, PixelColor};
-use uefi::proto::console::gop::PixelFormat;
+use alloc::{boxed::Box, collections::VecDeque, string::String, vec::Vec};
 
-pub struct ShadowBuffer {
-    buffer: alloc::vec::Vec<u8>,
-    pixels_per_scanline: usize,
-    horizontal_resolution: usize,
-    vertical_resolution: usize,
-    pixel_format: PixelFormat,
+use mikanos_rs_frame_buffer::{
+    DEFAULT_FONT, Font, FontMetrics, FrameBufferInfo, FrameBufferWriter, MemoryBuffer, PixelColor,
+    Psf2Font, PsfError, Rect,
+};
+use mikanos_rs_terminal::{unicode, vt100};
+
+/// Where the console gets its glyphs from.
+enum GlyphSource {
+    /// The built-in bitmap font, or a PSF2 font from the initrd. Its cell size is fixed.
+    Psf(Psf2Font<'static>),
+    /// `fonts/Tamzen7x14r.ttf` scaled to the `fontsize` parameter, or a TrueType font from the
+    /// initrd.
+    #[cfg(feature = "ttf")]
+    Ttf { font: fontdue::Font, size: f32 },
 }
 
-impl ShadowBuffer {
-    pub fn new(
-        pixels_per_scanline: usize,
-        horizontal_resolution: usize,
-        vertical_resolution: usize,
-        pixel_format: PixelFormat,
-    ) -> Self {
-        let bufsize = 4 * pixels_per_scanline * vertical_resolution;
-        Self {
-            buffer: alloc::vec![0; bufsize],
-            pixels_per_scanline,
-            horizontal_resolution,
-            vertical_resolution,
-            pixel_format,
+impl GlyphSource {
+    fn new(font_size: f32) -> Self {
+        #[cfg(feature = "ttf")]
+        {
+            let raw_font = include_bytes!("../fonts/Tamzen7x14r.ttf") as &[u8];
+            match fontdue::Font::from_bytes(raw_font, fontdue::FontSettings::default()) {
+                Ok(font) => {
+                    return Self::Ttf {
+                        font,
+                        size: font_size,
+                    };
+                }
+                Err(e) => {
+                    crate::serial_println!(
+                        "Failed to load the TTF font, using the built-in one: {}",
+                        e
+                    );
+                }
+            }
         }
+        #[cfg(not(feature = "ttf"))]
+        let _ = font_size;
+        Self::Psf(Psf2Font::parse(DEFAULT_FONT).expect("the built-in font is broken"))
     }
-}
 
-impl FrameBufferWriter for ShadowBuffer {
-    fn get_buffer_mut(&self) -> *mut u8 {
-        self.buffer.as_ptr() as *mut u8
+    /// Loads a fallback font from the initrd. TrueType fonts are rendered at `size` pixels.
+    fn load(path: &str, size: f32) -> Option<Self> {
+        let Some(data) = crate::initrd::find_file(path) else {
+            crate::serial_println!("font: {} is not in the initrd", path);
+            return None;
+        };
+        let err = match Psf2Font::parse(data) {
+            Ok(font) => return Some(Self::Psf(font)),
+            Err(err) => err,
+        };
+        #[cfg(feature = "ttf")]
+        if err == PsfError::InvalidMagic {
+            return match fontdue::Font::from_bytes(data, fontdue::FontSettings::default()) {
+                Ok(font) => Some(Self::Ttf { font, size }),
+                Err(err) => {
+                    crate::serial_println!("font: {}: {}", path, err);
+                    None
+                }
+            };
+        }
+        #[cfg(not(feature = "ttf"))]
+        if err == PsfError::InvalidMagic {
+            let _ = size;
+            crate::serial_println!("font: {}: TrueType fonts need the ttf feature", path);
+            return None;
+        }
+        crate::serial_println!("font: {}: {}", path, err);
+        None
     }
 
-    fn size(&self) -> usize {
-        4 * self.pixels_per_scanline * self.vertical_resolution
+    fn has_glyph(&self, c: char) -> bool {
+        match self {
+            Self::Psf(font) => font.has_glyph(c),
+            #[cfg(feature = "ttf")]
+            Self::Ttf { font, .. } => font.lookup_glyph_index(c) != 0,
+        }
     }
 
-    fn get_pixel_format(&self) -> PixelFormat {
-        self.pixel_format
+    /// Returns the cell width, cell height and ascent.
+    fn cell_metrics(&self) -> (usize, usize, usize) {
+        match self {
+            Self::Psf(font) => (font.width(), font.height(), font.ascent()),
+            #[cfg(feature = "ttf")]
+            Self::Ttf { font, size } => {
+                let line_metrics = font.horizontal_line_metrics(*size).unwrap();
+                // The font is monospaced, so any glyph's advance is the cell width.
+                (
+                    ceil_to_usize(font.metrics('M', *size).advance_width).max(1),
+                    ceil_to_usize(line_metrics.new_line_size).max(1),
+                    ceil_to_usize(line_metrics.ascent),
+                )
+            }
+        }
     }
 
-    fn get_pixels_per_scan_line(&self) -> usize {
-        self.pixels_per_scanline
+    fn rasterize(&self, c: char) -> (FontMetrics, Vec<u8>) {
+        match self {
+            Self::Psf(font) => font
+                .rasterize(c)
+                .unwrap_or_else(|| (FontMetrics::new(0, 0, 0, 0), Vec::new())),
+            #[cfg(feature = "ttf")]
+            Self::Ttf { font, size } => {
+                let (metrics, bitmap) = font.rasterize(c, *size);
+                let metrics =
+                    FontMetrics::new(metrics.xmin, metrics.ymin, metrics.width, metrics.height);
+                (metrics, bitmap)
+            }
+        }
     }
+}
 
-    fn get_horizontal_resolution(&self) -> usize {
-        self.horizontal_resolution
+/// The primary font followed by fallback fonts for the characters it lacks.
+struct GlyphChain {
+    sources: Vec<GlyphSource>,
+}
+
+impl GlyphChain {
+    fn primary(&self) -> &GlyphSource {
+        &self.sources[0]
     }
 
-    fn get_vertical_resolution(&self) -> usize {
-        self.vertical_resolution
+    /// Rasterizes `c` with the first font that has it, or draws `?` with the primary font.
+    fn rasterize(&self, c: char) -> (FontMetrics, Vec<u8>) {
+        match self.sources.iter().find(|source| source.has_glyph(c)) {
+            Some(source) => source.rasterize(c),
+            None => self.primary().rasterize('?'),
+        }
     }
 }
 
-const CHAR_WIDTH: usize = 8;
-const CHAR_HEIGHT: usize = 16;
+/// How text is drawn, set by SGR escape sequences.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Attributes {
+    /// `None` means the console's default color. For the background, that shows the wallpaper.
+    fg: Option<PixelColor>,
+    bg: Option<PixelColor>,
+    bold: bool,
+    reverse: bool,
+}
 
+impl Attributes {
+    const DEFAULT: Self = Self {
+        fg: None,
+        bg: None,
+        bold: false,
+        reverse: false,
+    };
+}
+
+#[derive(Clone, Copy)]
+struct Cell {
+    /// `CONTINUATION` in the right half of a double-width character.
+    c: char,
+    attributes: Attributes,
+}
+
+const CONTINUATION: char = '\0';
+
+impl Cell {
+    /// An erased cell. Like xterm, it keeps the current background color only.
+    const fn blank(attributes: &Attributes) -> Self {
+        Self {
+            c: ' ',
+            attributes: Attributes {
+                bg: attributes.bg,
+                ..Attributes::DEFAULT
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SavedCursor {
+    row: usize,
+    col: usize,
+    attributes: Attributes,
+}
+
+/// A text console that understands a subset of VT100 escape sequences. See `perform`.
 pub struct Console {
-    shadow_buffer: ShadowBuffer,
+    shadow_buffer: MemoryBuffer,
+    /// The background color with the wallpaper centered on it. Without a wallpaper, cells are
+    /// cleared with `bg_color` instead.
+    background: Option<MemoryBuffer>,
     fg_color: PixelColor,
     bg_color: PixelColor,
     cursor_row: usize,
     cursor_col: usize,
-    font_data: fontdue::Font,
-    font_cache: hashbrown::HashMap<char, (fontdue::Metrics, alloc::vec::Vec<u8>)>,
+    n_rows: usize,
+    n_cols: usize,
+    /// The characters on screen, `n_cols` per row, for redrawing the text over the wallpaper.
+    cells: Vec<Cell>,
+    /// Rows that scrolled off the top, oldest first. At most `scrollback_lines` are kept.
+    history: VecDeque<Box<[Cell]>>,
+    scrollback_lines: usize,
+    /// How many rows of `history` the view is scrolled back by. Output snaps it back to 0.
+    view_offset: usize,
+    /// Set when the whole screen has to be redrawn, e.g. after text scrolled over the wallpaper.
+    /// Cells aren't drawn individually until `put_string` redraws everything once at its end.
+    redraw_pending: bool,
+    parser: vt100::Parser,
+    attributes: Attributes,
+    saved_cursor: Option<SavedCursor>,
+    /// Size of a character cell, derived from the font.
+    char_width: usize,
+    char_height: usize,
+    /// Distance from the top of a cell to the baseline.
+    ascent: usize,
+    glyphs: GlyphChain,
+    font_cache: hashbrown::HashMap<char, (FontMetrics, Vec<u8>)>,
 }
 
 impl Console {
-    const N_ROWS: usize = 37;
-    const N_COLS: usize = 100;
     pub fn new(
-        frame_buffer: &'static FrameBuffer,
+        frame_buffer: &FrameBufferInfo,
         fg_color: PixelColor,
         bg_color: PixelColor,
+        font_size: f32,
+        fallback_fonts: &[String],
+        scrollback_lines: usize,
+        wallpaper: Option<MemoryBuffer>,
     ) -> Self {
-        let shadow_buffer = ShadowBuffer::new(
-            frame_buffer.get_pixels_per_scan_line(),
-            frame_buffer.get_horizontal_resolution(),
-            frame_buffer.get_vertical_resolution(),
-            frame_buffer.get_pixel_format(),
-        );
+        let mut shadow_buffer = shadow_buffer_for(frame_buffer);
         shadow_buffer.fill(&bg_color);
-        let raw_font = include_bytes!("../fonts/Tamzen7x14r.ttf") as &[u8];
-        let font_data =
-            fontdue::Font::from_bytes(raw_font, fontdue::FontSettings::default()).unwrap();
+        let background = wallpaper.map(|wallpaper| {
+            let x = center(
+                shadow_buffer.get_horizontal_resolution(),
+                wallpaper.get_horizontal_resolution(),
+            );
+            let y = center(
+                shadow_buffer.get_vertical_resolution(),
+                wallpaper.get_vertical_resolution(),
+            );
+            shadow_buffer.blit(&wallpaper, wallpaper.bounds(), x, y);
+            let mut background = shadow_buffer_for(frame_buffer);
+            copy_buffer(&shadow_buffer, &mut background);
+            background
+        });
+        let primary = GlyphSource::new(font_size);
+        let (char_width, char_height, ascent) = primary.cell_metrics();
+        // Size TrueType fallbacks so that ideographs fill a double-width cell.
+        let fallback_size = char_height.min(2 * char_width) as f32;
+        let mut sources = alloc::vec![primary];
+        sources.extend(
+            fallback_fonts
+                .iter()
+                .filter_map(|path| GlyphSource::load(path, fallback_size)),
+        );
+        let glyphs = GlyphChain { sources };
+        // Keep at least one cell even if a large font doesn't fit the screen. It is clipped then.
+        let n_rows = (shadow_buffer.get_vertical_resolution() / char_height).max(1);
+        let n_cols = (shadow_buffer.get_horizontal_resolution() / char_width).max(1);
         Self {
             shadow_buffer,
+            background,
             fg_color,
             bg_color,
             cursor_row: 0,
             cursor_col: 0,
-            font_data,
+            n_rows,
+            n_cols,
+            cells: alloc::vec![Cell::blank(&Attributes::DEFAULT); n_rows * n_cols],
+            history: VecDeque::new(),
+            scrollback_lines,
+            view_offset: 0,
+            redraw_pending: false,
+            parser: vt100::Parser::new(),
+            attributes: Attributes::DEFAULT,
+            saved_cursor: None,
+            char_width,
+            char_height,
+            ascent,
+            glyphs,
             font_cache: hashbrown::HashMap::new(),
         }
     }
     pub fn put_string(&mut self, s: &str) {
-        for c in s.as_bytes() {
-            if *c == b'\n' {
-                self.new_line();
-                continue;
+        if self.view_offset > 0 && !s.is_empty() {
+            self.view_offset = 0;
+            self.redraw_pending = true;
+        }
+        for c in s.chars() {
+            if let Some(action) = self.parser.advance(c) {
+                self.perform(action);
+            }
+        }
+        if self.redraw_pending {
+            self.redraw();
+        }
+    }
+    fn perform(&mut self, action: vt100::Action) {
+        match action {
+            vt100::Action::Print(c) => self.write_char(c),
+            vt100::Action::Execute(b'\n') => self.new_line(),
+            vt100::Action::Execute(b'\r') => self.cursor_col = 0,
+            vt100::Action::Execute(0x08) => self.cursor_col = self.cursor_col.saturating_sub(1),
+            vt100::Action::Execute(b'\t') => {
+                self.cursor_col = ((self.cursor_col / 8 + 1) * 8).min(self.n_cols - 1);
             }
-            self.write_byte(*c);
+            vt100::Action::Execute(_) => {}
+            vt100::Action::Esc(b'7') => self.save_cursor(),
+            vt100::Action::Esc(b'8') => self.restore_cursor(),
+            vt100::Action::Esc(b'c') => {
+                self.attributes = Attributes::DEFAULT;
+                self.saved_cursor = None;
+                self.erase(0, self.cells.len());
+                (self.cursor_row, self.cursor_col) = (0, 0);
+            }
+            vt100::Action::Esc(_) => {}
+            vt100::Action::Csi(csi) if !csi.private => self.perform_csi(&csi),
+            vt100::Action::Csi(_) => {}
         }
     }
-    fn write_byte(&mut self, b: u8) {
-        let x = CHAR_WIDTH * self.cursor_col;
-        let y = CHAR_HEIGHT * self.cursor_row;
+    fn perform_csi(&mut self, csi: &vt100::Csi) {
+        let n = csi.param(0, 1) as usize;
+        match csi.final_byte {
+            b'm' => self.select_graphic_rendition(csi),
+            // CUP
+            b'H' | b'f' => {
+                self.cursor_row = (csi.param(0, 1) as usize - 1).min(self.n_rows - 1);
+                self.cursor_col = (csi.param(1, 1) as usize - 1).min(self.n_cols - 1);
+            }
+            // CUU, CUD, CUF, CUB
+            b'A' => self.cursor_row = self.cursor_row.saturating_sub(n),
+            b'B' => self.cursor_row = (self.cursor_row + n).min(self.n_rows - 1),
+            b'C' => self.cursor_col = (self.cursor_col + n).min(self.n_cols - 1),
+            b'D' => self.cursor_col = self.cursor_col.saturating_sub(n),
+            // EL
+            b'K' => {
+                let line = self.cursor_row * self.n_cols;
+                let cursor = line + self.cursor_col;
+                match csi.param(0, 0) {
+                    0 => self.erase(cursor, line + self.n_cols),
+                    1 => self.erase(line, cursor + 1),
+                    2 => self.erase(line, line + self.n_cols),
+                    _ => {}
+                }
+            }
+            // ED
+            b'J' => {
+                let cursor = self.cursor_row * self.n_cols + self.cursor_col;
+                match csi.param(0, 0) {
+                    0 => self.erase(cursor, self.cells.len()),
+                    1 => self.erase(0, cursor + 1),
+                    2 => self.erase(0, self.cells.len()),
+                    3 => {
+                        self.history.clear();
+                        self.erase(0, self.cells.len());
+                    }
+                    _ => {}
+                }
+            }
+            b's' => self.save_cursor(),
+            b'u' => self.restore_cursor(),
+            _ => {}
+        }
+    }
+    fn select_graphic_rendition(&mut self, csi: &vt100::Csi) {
+        let attributes = &mut self.attributes;
+        for sgr in csi.sgr() {
+            match sgr {
+                vt100::Sgr::Reset => *attributes = Attributes::DEFAULT,
+                vt100::Sgr::Bold(bold) => attributes.bold = bold,
+                vt100::Sgr::Reverse(reverse) => attributes.reverse = reverse,
+                vt100::Sgr::Foreground(color) => attributes.fg = color.map(pixel_color),
+                vt100::Sgr::Background(color) => attributes.bg = color.map(pixel_color),
+            }
+        }
+    }
+    fn save_cursor(&mut self) {
+        self.saved_cursor = Some(SavedCursor {
+            row: self.cursor_row,
+            col: self.cursor_col,
+            attributes: self.attributes,
+        });
+    }
+    fn restore_cursor(&mut self) {
+        if let Some(saved) = self.saved_cursor {
+            self.cursor_row = saved.row;
+            self.cursor_col = saved.col;
+            self.attributes = saved.attributes;
+        }
+    }
+    /// Blanks the cells from index `start` up to `end` (exclusive).
+    fn erase(&mut self, start: usize, end: usize) {
+        let end = end.min(self.cells.len());
+        self.split_wide_chars(start, end);
+        let blank = Cell::blank(&self.attributes);
+        for i in start..end {
+            self.cells[i] = blank;
+            self.draw_cell(i / self.n_cols, i % self.n_cols);
+        }
+    }
+    /// Blanks the other half of any double-width character cut by the cells from `start` up to
+    /// `end`, which are about to be overwritten.
+    fn split_wide_chars(&mut self, start: usize, end: usize) {
+        if start % self.n_cols > 0 && self.cells[start].c == CONTINUATION {
+            self.cells[start - 1].c = ' ';
+            self.draw_cell((start - 1) / self.n_cols, (start - 1) % self.n_cols);
+        }
+        if end < self.cells.len() && self.cells[end].c == CONTINUATION {
+            self.cells[end].c = ' ';
+            self.draw_cell(end / self.n_cols, end % self.n_cols);
+        }
+    }
+    fn write_char(&mut self, c: char) {
+        let (c, width) = match unicode::char_width(c) {
+            // Combining characters are not composed with the previous one.
+            0 => return,
+            // A one-column screen can't hold a double-width character.
+            2 if self.n_cols < 2 => ('?', 1),
+            width => (c, width),
+        };
+        if self.cursor_col + width > self.n_cols {
+            self.new_line();
+        }
+        let index = self.cursor_row * self.n_cols + self.cursor_col;
+        self.split_wide_chars(index, index + width);
+        let attributes = self.attributes;
+        self.cells[index] = Cell { c, attributes };
+        if width == 2 {
+            self.cells[index + 1] = Cell {
+                c: CONTINUATION,
+                attributes,
+            };
+        }
+        self.draw_cell(self.cursor_row, self.cursor_col);
+        self.cursor_col += width;
+        if self.cursor_col == self.n_cols {
+            self.new_line();
+        }
+    }
+    fn cell_rect(&self, row: usize, col: usize) -> Rect {
+        Rect::new(
+            (self.char_width * col) as isize,
+            (self.char_height * row) as isize,
+            self.char_width,
+            self.char_height,
+        )
+    }
+    /// Returns screen row `row`, which comes from `history` while the view is scrolled back.
+    fn visible_row(&self, row: usize) -> &[Cell] {
+        if row < self.view_offset {
+            &self.history[self.history.len() - self.view_offset + row]
+        } else {
+            let start = (row - self.view_offset) * self.n_cols;
+            &self.cells[start..start + self.n_cols]
+        }
+    }
+    fn draw_cell(&mut self, row: usize, col: usize) {
+        if self.redraw_pending {
+            return;
+        }
+        let line = self.visible_row(row);
+        let cell = line[col];
+        if cell.c == CONTINUATION {
+            // Drawn together with the left half.
+            return;
+        }
+        let wide = line.get(col + 1).is_some_and(|next| next.c == CONTINUATION);
+        let mut rect = self.cell_rect(row, col);
+        if wide {
+            rect.width *= 2;
+        }
+        let attributes = &cell.attributes;
+        let mut fg = attributes.fg.unwrap_or(self.fg_color);
+        let mut bg = attributes.bg;
+        if attributes.reverse {
+            (fg, bg) = (bg.unwrap_or(self.bg_color), Some(fg));
+        }
+        match (bg, &self.background) {
+            (Some(bg), _) => self.shadow_buffer.fill_rect(rect, &bg),
+            (None, Some(background)) => self.shadow_buffer.blit(background, rect, rect.x, rect.y),
+            (None, None) => self.shadow_buffer.fill_rect(rect, &self.bg_color),
+        }
+        let c = cell.c;
+        if c == ' ' {
+            return;
+        }
         let (metrics, bitmap) = self
             .font_cache
-            .entry(b as char)
-            .or_insert_with(|| self.font_data.rasterize(b as char, 16.0));
-        let metrics = FontMetrics::new(metrics.xmin, metrics.ymin, metrics.width, metrics.height);
-        let font = Font::new(metrics, bitmap.as_ptr());
-        self.shadow_buffer.write_char(x, y, &font, &self.fg_color);
-        self.cursor_col += 1;
-        if self.cursor_col == Self::N_COLS {
-            self.new_line();
+            .entry(c)
+            .or_insert_with(|| self.glyphs.rasterize(c));
+        let font = Font::new(*metrics, bitmap);
+        let (x, baseline) = (rect.x as usize, rect.y as usize + self.ascent);
+        self.shadow_buffer.write_char(x, baseline, &font, &fg, None);
+        if attributes.bold {
+            // Overstrike one pixel to the right.
+            self.shadow_buffer
+                .write_char(x + 1, baseline, &font, &fg, None);
         }
     }
     fn new_line(&mut self) {
         self.cursor_col = 0;
-        if self.cursor_row < Self::N_ROWS - 1 {
+        if self.cursor_row < self.n_rows - 1 {
             self.cursor_row += 1;
         } else {
             self.scroll_line();
         }
     }
-    fn scroll_line(&mut self) {
-        unsafe {
-            let offset = 4 * self.shadow_buffer.get_pixels_per_scan_line() * CHAR_HEIGHT;
-            let src = self.shadow_buffer.get_buffer_mut().add(offset);
-            let dst = self.shadow_buffer.get_buffer_mut();
-            let count =
-                4 * self.shadow_buffer.get_pixels_per_scan_line() * CHAR_HEIGHT * Self::N_ROWS;
-            core::ptr::copy(src, dst, count);
-            for x in 0..self.shadow_buffer.get_horizontal_resolution() {
-                for y in
-                    ((Self::N_ROWS - 1) * CHAR_HEIGHT)..self.shadow_buffer.get_vertical_resolution()
-                {
-                    self.shadow_buffer.write_pixel(x, y, &self.bg_color)
-                }
+    fn redraw(&mut self) {
+        self.redraw_pending = false;
+        for row in 0..self.n_rows {
+            for col in 0..self.n_cols {
+                self.draw_cell(row, col);
             }
         }
     }
-    pub fn get_buffer(&mut self) -> &mut ShadowBuffer {
+    /// Moves the view `lines` rows back into the history, or forward if negative.
+    pub fn scroll_view(&mut self, lines: isize) {
+        let offset = self
+            .view_offset
+            .saturating_add_signed(lines)
+            .min(self.history.len());
+        if offset != self.view_offset {
+            self.view_offset = offset;
+            self.redraw();
+        }
+    }
+    /// Number of rows `scroll_view` should move by for Shift+PageUp and Shift+PageDown.
+    pub fn page_size(&self) -> usize {
+        self.n_rows
+    }
+    fn push_history(&mut self) {
+        if self.scrollback_lines == 0 {
+            return;
+        }
+        let top = &self.cells[..self.n_cols];
+        if self.history.len() == self.scrollback_lines {
+            // Reuse the oldest row instead of allocating a new one.
+            let mut line = self.history.pop_front().unwrap();
+            line.copy_from_slice(top);
+            self.history.push_back(line);
+        } else {
+            self.history.push_back(top.into());
+        }
+    }
+    fn scroll_line(&mut self) {
+        self.push_history();
+        self.cells.copy_within(self.n_cols.., 0);
+        let last_row = (self.n_rows - 1) * self.n_cols;
+        self.cells[last_row..].fill(Cell::blank(&Attributes::DEFAULT));
+        if self.background.is_some() {
+            // The wallpaper stays in place, so the text has to be redrawn over it.
+            self.redraw_pending = true;
+            return;
+        }
+        let width = self.shadow_buffer.get_horizontal_resolution();
+        let text_height = self.char_height * (self.n_rows - 1);
+        self.shadow_buffer.copy_within(
+            Rect::new(0, self.char_height as isize, width, text_height),
+            0,
+            0,
+        );
+        let bottom = self.shadow_buffer.get_vertical_resolution() - text_height;
+        self.shadow_buffer.fill_rect(
+            Rect::new(0, text_height as isize, width, bottom),
+            &self.bg_color,
+        );
+    }
+    pub fn get_buffer(&mut self) -> &mut MemoryBuffer {
         &mut self.shadow_buffer
     }
 }
 
-pub fn copy_buffer<T: FrameBufferWriter, U: FrameBufferWriter>(src: &T, dest: &U) {
-    assert_eq!(src.size(), dest.size());
-    let src = src.get_buffer_mut();
-    let dst = dest.get_buffer_mut();
-    let count = dest.size();
-    unsafe {
-        core::ptr::copy(src, dst, count);
+/// The xterm 256-color palette: 16 system colors, a 6x6x6 color cube and 24 grays.
+fn palette(index: u8) -> PixelColor {
+    const SYSTEM: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    match index {
+        0..=15 => {
+            let (r, g, b) = SYSTEM[index as usize];
+            PixelColor::new(r, g, b)
+        }
+        16..=231 => {
+            let i = (index - 16) as usize;
+            PixelColor::new(
+                CUBE_LEVELS[i / 36],
+                CUBE_LEVELS[i / 6 % 6],
+                CUBE_LEVELS[i % 6],
+            )
+        }
+        232..=255 => {
+            let level = 8 + 10 * (index - 232);
+            PixelColor::new(level, level, level)
+        }
+    }
+}
+
+fn pixel_color(color: vt100::Color) -> PixelColor {
+    match color {
+        vt100::Color::Indexed(index) => palette(index),
+        vt100::Color::Rgb(r, g, b) => PixelColor::new(r, g, b),
     }
 }
+
+/// Offset that centers `inner` pixels in `outer` pixels. Negative if `inner` is larger.
+fn center(outer: usize, inner: usize) -> isize {
+    (outer as isize - inner as isize) / 2
+}
+
+/// Rounds a non-negative size up. `f32::ceil` is not available in `core`.
+#[cfg(feature = "ttf")]
+fn ceil_to_usize(x: f32) -> usize {
+    let truncated = x as usize;
+    if (truncated as f32) < x {
+        truncated + 1
+    } else {
+        truncated
+    }
+}
+
+/// Creates an off-screen buffer with the same layout as the frame buffer, for `copy_buffer`.
+pub fn shadow_buffer_for(frame_buffer: &FrameBufferInfo) -> MemoryBuffer {
+    MemoryBuffer::with_stride(
+        frame_buffer.get_horizontal_resolution(),
+        frame_buffer.get_vertical_resolution(),
+        frame_buffer.get_pixels_per_scan_line(),
+        frame_buffer.get_pixel_layout(),
+    )
+}
+
+pub fn copy_buffer<T: FrameBufferWriter, U: FrameBufferWriter>(src: &T, dest: &mut U) {
+    dest.as_pixels_mut().copy_from_slice(src.as_pixels());
+}
@@ -11,6 +11,7 @@ pub enum Event {
     Invalid,
     XHCI,
     Timeout(u64, TimerValue), // timeout, value
+    Keyboard(crate::keyboard::KeyEvent),
 }
 
 impl Default for Event {
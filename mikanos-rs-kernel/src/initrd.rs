@@ -0,0 +1,89 @@
+use mikanos_rs_boot_info::PhysicalRange;
+
+// The initial ramdisk is a ustar archive.
+// ref: https://www.gnu.org/software/tar/manual/html_node/Standard.html
+const BLOCK_SIZE: usize = 512;
+
+static INITRD: spin::Once<&'static [u8]> = spin::Once::new();
+
+pub fn init(range: PhysicalRange) {
+    let image = if range.is_empty() {
+        &[]
+    } else {
+        unsafe { core::slice::from_raw_parts(range.start as *const u8, range.size as usize) }
+    };
+    INITRD.call_once(|| image);
+}
+
+pub fn get_image() -> &'static [u8] {
+    INITRD.get().copied().unwrap_or(&[])
+}
+
+pub struct File {
+    pub name: &'static str,
+    pub data: &'static [u8],
+}
+
+pub struct FileIter {
+    image: &'static [u8],
+    offset: usize,
+}
+
+fn trim_nul(field: &[u8]) -> &[u8] {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    &field[..len]
+}
+
+fn parse_octal(field: &[u8]) -> Option<usize> {
+    let s = core::str::from_utf8(trim_nul(field)).ok()?.trim();
+    usize::from_str_radix(s, 8).ok()
+}
+
+impl Iterator for FileIter {
+    type Item = File;
+
+    fn next(&mut self) -> Option<File> {
+        loop {
+            let header = self.image.get(self.offset..self.offset + BLOCK_SIZE)?;
+            // The archive ends with zero-filled blocks.
+            if header.iter().all(|&b| b == 0) || &header[257..262] != b"ustar" {
+                return None;
+            }
+            // A corrupt size field can be huge, so iteration ends on overflow.
+            let size = parse_octal(&header[124..136])?;
+            let data_start = self.offset.checked_add(BLOCK_SIZE)?;
+            let data = self.image.get(data_start..data_start.checked_add(size)?)?;
+            self.offset = size
+                .div_ceil(BLOCK_SIZE)
+                .checked_mul(BLOCK_SIZE)
+                .and_then(|padded| data_start.checked_add(padded))?;
+
+            let typeflag = header[156];
+            if typeflag != b'0' && typeflag != 0 {
+                // Skip directories, links and other non-regular entries.
+                continue;
+            }
+            // Long names are not supported, so only the name field (without prefix) is used.
+            let name = core::str::from_utf8(trim_nul(&header[0..100])).ok()?;
+            return Some(File { name, data });
+        }
+    }
+}
+
+pub fn files() -> FileIter {
+    FileIter {
+        image: get_image(),
+        offset: 0,
+    }
+}
+
+fn normalize(path: &str) -> &str {
+    path.trim_start_matches("./").trim_start_matches('/')
+}
+
+pub fn find_file(path: &str) -> Option<&'static [u8]> {
+    let path = normalize(path);
+    files()
+        .find(|file| normalize(file.name) == path)
+        .map(|file| file.data)
+}
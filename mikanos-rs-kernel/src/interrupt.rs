@@ -38,6 +38,19 @@ impl IDTAttribute {
 }
 
 pub type HandlerFunc = extern "x86-interrupt" fn();
+pub type HandlerFuncWithFrame = extern "x86-interrupt" fn(InterruptStackFrame);
+pub type HandlerFuncWithErrorCode = extern "x86-interrupt" fn(InterruptStackFrame, u64);
+
+/// What the CPU pushes on the stack before calling an interrupt handler (see Intel SDM Vol 3, 7.14.1).
+#[derive(Debug)]
+#[repr(C)]
+pub struct InterruptStackFrame {
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+}
 
 #[derive(Clone, Copy)]
 #[repr(C)]
@@ -64,8 +77,19 @@ impl InterruptDescriptor {
     }
     #[inline]
     pub fn new(attr: IDTAttribute, handler: HandlerFunc) -> Self {
+        Self::from_offset(attr, handler as usize as u64)
+    }
+    #[inline]
+    pub fn with_frame(attr: IDTAttribute, handler: HandlerFuncWithFrame) -> Self {
+        Self::from_offset(attr, handler as usize as u64)
+    }
+    #[inline]
+    pub fn with_error_code(attr: IDTAttribute, handler: HandlerFuncWithErrorCode) -> Self {
+        Self::from_offset(attr, handler as usize as u64)
+    }
+    #[inline]
+    fn from_offset(attr: IDTAttribute, offset: u64) -> Self {
         let mut cs: u16;
-        let offset = handler as u64;
         // Get the current value of the code-segment register.
         unsafe { core::arch::asm!("mov ax, cs", out("ax") cs) }
         Self {
@@ -117,14 +141,14 @@ pub fn init_idt() {
     unsafe {
         IDT.set_entry(
             InterruptVector::DivisionError as usize,
-            InterruptDescriptor::new(
+            InterruptDescriptor::with_frame(
                 IDTAttribute::new(SystemDescriptorType::InterruptGate, 0),
                 handle_division_error,
             ),
         );
         IDT.set_entry(
             InterruptVector::DoubleFault as usize,
-            InterruptDescriptor::new(
+            InterruptDescriptor::with_error_code(
                 IDTAttribute::new(SystemDescriptorType::InterruptGate, 0),
                 handle_double_fault,
             ),
@@ -173,13 +197,19 @@ fn notify_end_of_interrupt() {
     unsafe { *eoi_reg = 0 };
 }
 
-extern "x86-interrupt" fn handle_division_error() {
-    crate::serial_println!("Zero division detected!");
+extern "x86-interrupt" fn handle_division_error(frame: InterruptStackFrame) {
+    crate::serial_println!(
+        "Zero division detected at {}!",
+        crate::symbols::Symbolized(frame.rip)
+    );
     panic!();
 }
 
-extern "x86-interrupt" fn handle_double_fault() {
-    crate::serial_println!("Double fault detected!");
+extern "x86-interrupt" fn handle_double_fault(frame: InterruptStackFrame, _error_code: u64) {
+    crate::serial_println!(
+        "Double fault detected at {}!",
+        crate::symbols::Symbolized(frame.rip)
+    );
     panic!();
 }
 
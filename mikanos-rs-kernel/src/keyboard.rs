@@ -0,0 +1,39 @@
+use crate::event::Event;
+
+/// HID keyboard usage IDs (USB HID Usage Tables, section 10).
+pub const KEY_PRINT_SCREEN: u8 = 0x46;
+pub const KEY_PAGE_UP: u8 = 0x4b;
+pub const KEY_PAGE_DOWN: u8 = 0x4e;
+
+/// Bits of `KeyEvent::modifier`.
+const MODIFIER_LEFT_SHIFT: u8 = 0x02;
+const MODIFIER_RIGHT_SHIFT: u8 = 0x20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// Modifier bits of the HID boot protocol report.
+    pub modifier: u8,
+    pub keycode: u8,
+    pub press: bool,
+}
+
+impl KeyEvent {
+    pub fn shift(&self) -> bool {
+        self.modifier & (MODIFIER_LEFT_SHIFT | MODIFIER_RIGHT_SHIFT) != 0
+    }
+}
+
+/// Called by the USB keyboard driver while the main task processes xHCI events.
+pub extern "C" fn observer(modifier: u8, keycode: u8, press: bool) {
+    let event = Event::Keyboard(KeyEvent {
+        modifier,
+        keycode,
+        press,
+    });
+    let pushed = x86_64::instructions::interrupts::without_interrupts(|| unsafe {
+        crate::event::get_event_queue_raw().lock().push(event)
+    });
+    if pushed.is_err() {
+        crate::serial_println!("Event queue is full, dropping a keyboard event.");
+    }
+}
@@ -3,35 +3,43 @@
 #![feature(abi_x86_interrupt, naked_functions)]
 extern crate alloc;
 
+mod acpi;
 mod allocator;
 mod console;
 mod descriptor;
 mod event;
+mod initrd;
 #[allow(static_mut_refs)]
 mod interrupt;
+mod keyboard;
 mod memory_manager;
 mod mouse;
 mod paging;
+mod params;
 mod pci;
 mod queue;
+mod screenshot;
 mod segment;
 mod serial;
+mod symbols;
 mod task;
 mod timer;
+mod wallpaper;
 mod xhci;
 
-use console::{Console, ShadowBuffer, copy_buffer};
+use console::{Console, copy_buffer, shadow_buffer_for};
 use core::panic::PanicInfo;
 use interrupt::{disable_maskable_interrupts, enable_maskable_interrupts};
-use mikanos_rs_frame_buffer::{FrameBuffer, FrameBufferWriter, PixelColor};
+use mikanos_rs_boot_info::BootInfo;
+use mikanos_rs_frame_buffer::{FrameBuffer, PixelColor};
 use mouse::{MouseEvent, init_mouse};
-use uefi::mem::memory_map::MemoryMapOwned;
 use x86_64::instructions::interrupts::without_interrupts;
 use xhci::{get_xhc, init_xhc};
 
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
-    serial_println!("Panic!");
+fn panic(info: &PanicInfo) -> ! {
+    serial_println!("Panic! {}", info);
+    symbols::print_backtrace();
     loop {}
 }
 
@@ -52,19 +60,19 @@ impl KernelStack {
 
 const _KERNEL_MAIN_STACK: KernelStack = KernelStack::new();
 
+// The loader's copy of BootInfo lives in memory that the kernel will reuse, so keep our own.
+static mut BOOT_INFO: core::cell::OnceCell<BootInfo> = core::cell::OnceCell::new();
+
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn kernel_main(
-    frame_buffer: &'static FrameBuffer,
-    memory_map: &'static MemoryMapOwned,
-) {
+pub unsafe extern "C" fn kernel_main(boot_info: &'static BootInfo) {
     let stack_top = _KERNEL_MAIN_STACK.end_addr();
+    symbols::set_kernel_stack(stack_top - size_of::<KernelStack>() as u64..stack_top);
     unsafe {
         core::arch::asm!(
             "mov rsp, {0}",
             "call kernel_main_new_stack",
             in(reg) stack_top,
-            in("rdi") frame_buffer,
-            in("rsi") memory_map,
+            in("rdi") boot_info,
             clobber_abi("C"),
         );
     }
@@ -72,27 +80,99 @@ pub unsafe extern "C" fn kernel_main(
 
 #[unsafe(no_mangle)]
 #[allow(static_mut_refs)]
-pub extern "C" fn kernel_main_new_stack(
-    frame_buffer: &'static FrameBuffer,
-    memory_map: &'static MemoryMapOwned,
-) {
+pub extern "C" fn kernel_main_new_stack(boot_info: &'static BootInfo) {
+    if let Err(err) = boot_info.validate() {
+        serial_println!("Incompatible boot loader: {:?}", err);
+        loop {
+            x86_64::instructions::hlt();
+        }
+    }
+    let boot_info: &'static BootInfo = unsafe {
+        BOOT_INFO.set(*boot_info).ok().unwrap();
+        BOOT_INFO.get().unwrap()
+    };
+    let frame_buffer_info = &boot_info.frame_buffer;
+    symbols::init(
+        boot_info.kernel_symtab,
+        boot_info.kernel_strtab,
+        boot_info.kernel_load_bias,
+    );
+    serial_println!(
+        "Kernel image: {:#x} - {:#x} (load bias {:#x})",
+        boot_info.kernel_image.start,
+        boot_info.kernel_image.end(),
+        boot_info.kernel_load_bias
+    );
+
     unsafe {
         segment::init_gdt();
         paging::setup_identity_page_table();
         interrupt::init_idt();
-        memory_manager::init(memory_map);
+        // The kernel image is loader data. The others are allocated with their own memory types,
+        // but reserve them explicitly as well so that they survive any change in how the memory
+        // map is interpreted.
+        memory_manager::init(boot_info.memory_map(), &[
+            boot_info.kernel_image,
+            boot_info.initrd,
+            boot_info.kernel_symtab,
+            boot_info.kernel_strtab,
+        ]);
         allocator::init_heap();
-        timer::init_local_apic_timer();
     }
 
+    let params = params::init(unsafe { boot_info.cmdline() });
+    serial_println!("Kernel parameters: {:?}", params);
+    xhci::set_log_level(params.log_level);
+
+    match unsafe { acpi::init(boot_info.acpi_rsdp) } {
+        Ok(()) => {
+            for table in acpi::tables() {
+                serial_println!("ACPI table: {}", table.signature());
+            }
+            let num_frames = memory_manager::reclaim(memory_manager::RegionKind::AcpiReclaim);
+            serial_println!("Reclaimed {} frames of ACPI memory.", num_frames);
+        }
+        Err(err) => {
+            serial_println!("ACPI is not available: {}", err);
+        }
+    }
+
+    // The command line has been parsed and the memory map consumed, so nothing in the loader's
+    // memory is needed anymore.
+    let num_frames = memory_manager::reclaim(memory_manager::RegionKind::Loader);
+    serial_println!("Reclaimed {} frames of loader memory.", num_frames);
+
+    unsafe { timer::init_local_apic_timer() };
+
+    initrd::init(boot_info.initrd);
+    for file in initrd::files() {
+        serial_println!("initrd: {} ({} bytes)", file.name, file.data.len());
+    }
+    if let Some(init) = &params.init {
+        match initrd::find_file(init) {
+            Some(data) => serial_println!("init: {} ({} bytes)", init, data.len()),
+            None => serial_println!("init: {} is not in the initrd", init),
+        }
+    }
+
+    serial_println!(
+        "EFI system table: {:#x}, runtime services: {:#x}",
+        boot_info.efi_system_table,
+        boot_info.efi_runtime_services
+    );
+
     let mut console = Console::new(
-        &frame_buffer,
+        frame_buffer_info,
         PixelColor::new(0, 0, 0),
         PixelColor::new(255, 255, 255),
+        params.font_size,
+        &params.fallback_fonts,
+        params.scrollback_lines,
+        wallpaper::load(params.wallpaper.as_deref(), frame_buffer_info),
     );
 
-    let screen_width = frame_buffer.get_horizontal_resolution();
-    let screen_height = frame_buffer.get_vertical_resolution();
+    let screen_width = frame_buffer_info.get_horizontal_resolution();
+    let screen_height = frame_buffer_info.get_vertical_resolution();
     init_mouse((200, 300), (screen_width, screen_height));
     for _ in 0..100 {
         let dummy_event = MouseEvent::new(0, -10, 0);
@@ -145,22 +225,22 @@ pub extern "C" fn kernel_main_new_stack(
                 .set_consumer(main_task_id)
         })
     }
-    let task_b_c_priority = 10;
-    let task_b_id = task::add_task(task::Task::new(
-        task::TaskDescriptor::Func(task::task_b),
-        task_b_c_priority,
-    ));
-    let task_c_id = task::add_task(task::Task::new(
-        task::TaskDescriptor::Func(task::task_c),
-        task_b_c_priority,
-    ));
-
-    let mut shadow_buffer = ShadowBuffer::new(
-        frame_buffer.get_pixels_per_scan_line(),
-        frame_buffer.get_horizontal_resolution(),
-        frame_buffer.get_vertical_resolution(),
-        frame_buffer.get_pixel_format(),
-    );
+    let demo_tasks = params.demo_tasks.then(|| {
+        let task_b_c_priority = 10;
+        let task_b_id = task::add_task(task::Task::new(
+            task::TaskDescriptor::Func(task::task_b),
+            task_b_c_priority,
+        ));
+        let task_c_id = task::add_task(task::Task::new(
+            task::TaskDescriptor::Func(task::task_c),
+            task_b_c_priority,
+        ));
+        (task_b_id, task_c_id)
+    });
+
+    let mut shadow_buffer = shadow_buffer_for(frame_buffer_info);
+    // Only the main loop below draws to the screen.
+    let mut frame_buffer = unsafe { FrameBuffer::new(*frame_buffer_info) };
 
     // Start responding hardware and timer interrupts.
     enable_maskable_interrupts();
@@ -169,6 +249,8 @@ pub extern "C" fn kernel_main_new_stack(
 
     console.put_string("Started!\n");
 
+    let mut debug_commands = serial::LineReader::new();
+
     // main event loop
     let mut cnt = 0;
     loop {
@@ -177,24 +259,33 @@ pub extern "C" fn kernel_main_new_stack(
             let msg = alloc::format!("(Task A) count={}\n", cnt);
             serial_print!("{}", msg);
         }
-        if cnt == 200 {
-            crate::serial_println!("Sleep Task C...");
-            task::sleep_task(&task_c_id);
-        }
-        if cnt == 400 {
-            crate::serial_println!("Sleep Task B...");
-            task::sleep_task(&task_b_id);
-        }
-        if cnt == 2000 {
-            crate::serial_println!("Wake-up Tasks...");
-            task::wake_up_task(&task_b_id);
-            task::wake_up_task(&task_c_id);
+        if let Some((task_b_id, task_c_id)) = &demo_tasks {
+            if cnt == 200 {
+                crate::serial_println!("Sleep Task C...");
+                task::sleep_task(task_c_id);
+            }
+            if cnt == 400 {
+                crate::serial_println!("Sleep Task B...");
+                task::sleep_task(task_b_id);
+            }
+            if cnt == 2000 {
+                crate::serial_println!("Wake-up Tasks...");
+                task::wake_up_task(task_b_id);
+                task::wake_up_task(task_c_id);
+            }
         }
 
         // Draw screen
-        copy_buffer(console.get_buffer(), &shadow_buffer);
+        copy_buffer(console.get_buffer(), &mut shadow_buffer);
         mouse::get_mouse().lock().draw_mouse(&mut shadow_buffer);
-        copy_buffer(&shadow_buffer, frame_buffer);
+        copy_buffer(&shadow_buffer, &mut frame_buffer);
+
+        if let Some(command) = debug_commands.poll() {
+            match command.trim() {
+                "screenshot" => screenshot::dump(&shadow_buffer),
+                command => serial_println!("Unknown debug command: {:?}", command),
+            }
+        }
 
         // Prevent interrupted between is_empty() check and sleep_task().
         disable_maskable_interrupts();
@@ -241,6 +332,16 @@ pub extern "C" fn kernel_main_new_stack(
                     }
                 }
             }
+            event::Event::Keyboard(key) if key.press => {
+                let page = console.page_size() as isize;
+                match key.keycode {
+                    keyboard::KEY_PRINT_SCREEN => screenshot::dump(&shadow_buffer),
+                    keyboard::KEY_PAGE_UP if key.shift() => console.scroll_view(page),
+                    keyboard::KEY_PAGE_DOWN if key.shift() => console.scroll_view(-page),
+                    _ => {}
+                }
+            }
+            event::Event::Keyboard(_) => {}
             event::Event::Invalid => {
                 serial_println!("invalid event!!");
                 panic!()
@@ -1,3 +1,4 @@
+use mikanos_rs_boot_info::PhysicalRange;
 use uefi::mem::memory_map::{MemoryMap, MemoryMapOwned, MemoryType};
 pub const PAGE_SIZE: usize = 4 * 1024;
 const MAX_PHYSICAL_MEM_SIZE: usize = 64 * 1024 * 1024 * 1024;
@@ -60,7 +61,6 @@ impl BitmapMemoryManager {
             start_frame_id = start_frame_id.offset(i + 1)
         }
     }
-    #[allow(unused)]
     pub fn free(&mut self, start_frame: FrameID, num_frames: usize) {
         assert!(self.is_initialized);
         assert!(
@@ -102,35 +102,176 @@ impl BitmapMemoryManager {
 pub static MEMORY_MANAGER: spin::Mutex<BitmapMemoryManager> =
     spin::Mutex::new(BitmapMemoryManager::new());
 
-pub fn init(memory_map: &'static MemoryMapOwned) {
-    let mut available_end = 0;
+/// What the kernel may do with a memory region reported by UEFI.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegionKind {
+    Free,
+    /// The loader's code and data, including the memory map and the kernel's ELF image. See
+    /// `reclaim`.
+    Loader,
+    /// ACPI tables. Reusable once `acpi::init` has copied them.
+    AcpiReclaim,
+    Reserved,
+}
+
+impl RegionKind {
+    fn of(ty: MemoryType) -> Self {
+        match ty {
+            MemoryType::CONVENTIONAL
+            | MemoryType::BOOT_SERVICES_CODE
+            | MemoryType::BOOT_SERVICES_DATA => Self::Free,
+            MemoryType::LOADER_CODE | MemoryType::LOADER_DATA => Self::Loader,
+            MemoryType::ACPI_RECLAIM => Self::AcpiReclaim,
+            _ => Self::Reserved,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Region {
+    start: u64,
+    end: u64,
+    kind: RegionKind,
+}
+
+impl Region {
+    const EMPTY: Self = Self {
+        start: 0,
+        end: 0,
+        kind: RegionKind::Reserved,
+    };
+
+    fn frames(&self) -> (FrameID, usize) {
+        let start = FrameID((self.start / PAGE_SIZE as u64) as usize);
+        let end = FrameID(self.end.div_ceil(PAGE_SIZE as u64) as usize);
+        (start, end.0 - start.0)
+    }
+}
+
+/// Fixed-size storage, since the heap is carved out of the memory described here.
+struct Regions<const N: usize> {
+    regions: [Region; N],
+    len: usize,
+}
+
+impl<const N: usize> Regions<N> {
+    const fn new() -> Self {
+        Self {
+            regions: [Region::EMPTY; N],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, region: Region) {
+        assert!(self.len < N, "too many memory regions");
+        self.regions[self.len] = region;
+        self.len += 1;
+    }
+
+    fn as_slice(&self) -> &[Region] {
+        &self.regions[..self.len]
+    }
+}
+
+const MAX_REGIONS: usize = 512;
+const MAX_RESERVED: usize = 16;
+
+/// Loader and ACPI regions kept for `reclaim`, and ranges that must never be freed.
+static RECLAIMABLE: spin::Mutex<Regions<MAX_REGIONS>> = spin::Mutex::new(Regions::new());
+static RESERVED: spin::Mutex<Regions<MAX_RESERVED>> = spin::Mutex::new(Regions::new());
+
+/// Sorts the memory map by address and merges adjacent regions of the same kind.
+fn collect_regions(memory_map: &MemoryMapOwned) -> Regions<MAX_REGIONS> {
+    let mut regions = Regions::<MAX_REGIONS>::new();
     for desc in memory_map.entries() {
-        if available_end < desc.phys_start {
-            let start_frame_id = FrameID((available_end / PAGE_SIZE as u64) as usize);
-            let num_pages = ((desc.phys_start - available_end) / PAGE_SIZE as u64) as usize;
-            MEMORY_MANAGER
-                .lock()
-                .mark_allocated(start_frame_id, num_pages);
+        let start = desc.phys_start.min(MAX_PHYSICAL_MEM_SIZE as u64);
+        let end = (desc.phys_start + desc.page_count * uefi::boot::PAGE_SIZE as u64)
+            .min(MAX_PHYSICAL_MEM_SIZE as u64);
+        if start < end {
+            regions.push(Region {
+                start,
+                end,
+                kind: RegionKind::of(desc.ty),
+            });
         }
-        if desc.ty == MemoryType::CONVENTIONAL
-            || desc.ty == MemoryType::BOOT_SERVICES_CODE
-            || desc.ty == MemoryType::BOOT_SERVICES_DATA
-        {
-            available_end = desc.phys_start + desc.page_count * uefi::boot::PAGE_SIZE as u64;
-        } else {
-            let start_frame_id = FrameID((desc.phys_start / PAGE_SIZE as u64) as usize);
-            let num_pages = desc.page_count as usize * uefi::boot::PAGE_SIZE / PAGE_SIZE;
-            MEMORY_MANAGER
-                .lock()
-                .mark_allocated(start_frame_id, num_pages);
-            available_end = desc.phys_start + desc.page_count * uefi::boot::PAGE_SIZE as u64;
+    }
+    regions.regions[..regions.len].sort_unstable_by_key(|region| region.start);
+
+    let mut merged = Regions::<MAX_REGIONS>::new();
+    for &region in regions.as_slice() {
+        match merged.regions[..merged.len].last_mut() {
+            Some(last) if last.kind == region.kind && region.start <= last.end => {
+                last.end = last.end.max(region.end);
+            }
+            _ => merged.push(region),
         }
     }
-    MEMORY_MANAGER.lock().set_memory_range(
+    merged
+}
+
+/// Builds the frame bitmap from the UEFI memory map.
+///
+/// Descriptors don't need to be sorted or contiguous. Gaps and overlaps are treated as allocated.
+/// Frames in `reserved` stay allocated even if the memory map reports them as available or they
+/// are reclaimed later.
+pub fn init(memory_map: &'static MemoryMapOwned, reserved: &[PhysicalRange]) {
+    let regions = collect_regions(memory_map);
+    let mut manager = MEMORY_MANAGER.lock();
+    let mut available_end = 0;
+    for region in regions.as_slice() {
+        if available_end < region.start {
+            let gap = Region {
+                start: available_end,
+                end: region.start,
+                kind: RegionKind::Reserved,
+            };
+            let (start_frame_id, num_frames) = gap.frames();
+            manager.mark_allocated(start_frame_id, num_frames);
+        }
+        if region.kind != RegionKind::Free {
+            let (start_frame_id, num_frames) = region.frames();
+            manager.mark_allocated(start_frame_id, num_frames);
+        }
+        if matches!(region.kind, RegionKind::Loader | RegionKind::AcpiReclaim) {
+            RECLAIMABLE.lock().push(*region);
+        }
+        available_end = available_end.max(region.end);
+    }
+    for range in reserved.iter().filter(|range| !range.is_empty()) {
+        let region = Region {
+            start: range.start,
+            end: range.end(),
+            kind: RegionKind::Reserved,
+        };
+        let (start_frame_id, num_frames) = region.frames();
+        manager.mark_allocated(start_frame_id, num_frames);
+        RESERVED.lock().push(region);
+    }
+    manager.set_memory_range(
         FrameID(1),
         FrameID((available_end / PAGE_SIZE as u64) as usize),
     );
     unsafe {
-        MEMORY_MANAGER.lock().set_init();
+        manager.set_init();
+    }
+}
+
+/// Returns the regions of `kind` to the free pool, except for the ranges reserved in `init`.
+///
+/// Reclaiming `RegionKind::Loader` invalidates `BootInfo::memory_map` and `BootInfo::cmdline`.
+pub fn reclaim(kind: RegionKind) -> usize {
+    let mut manager = MEMORY_MANAGER.lock();
+    let mut num_reclaimed = 0;
+    for region in RECLAIMABLE.lock().as_slice() {
+        if region.kind == kind {
+            let (start_frame_id, num_frames) = region.frames();
+            manager.free(start_frame_id, num_frames);
+            num_reclaimed += num_frames;
+        }
+    }
+    for region in RESERVED.lock().as_slice() {
+        let (start_frame_id, num_frames) = region.frames();
+        manager.mark_allocated(start_frame_id, num_frames);
     }
+    num_reclaimed
 }
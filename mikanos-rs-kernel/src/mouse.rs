@@ -1,4 +1,4 @@
-use mikanos_rs_frame_buffer::{FrameBufferWriter, PixelColor};
+use mikanos_rs_frame_buffer::{FrameBufferWriter, draw_cursor};
 
 pub struct MouseEvent {
     _buttons: u8,
@@ -32,35 +32,6 @@ pub fn get_mouse() -> &'static spin::Mutex<Mouse> {
     MOUSE.get().unwrap()
 }
 
-const MOUSE_CURSOR_WIDTH: usize = 15;
-const MOUSE_CURSOR_HEIGHT: usize = 24;
-const MOUSE_CURSOR: [&'static str; MOUSE_CURSOR_HEIGHT] = [
-    "@              ",
-    "@@             ",
-    "@.@            ",
-    "@..@           ",
-    "@...@          ",
-    "@....@         ",
-    "@.....@        ",
-    "@......@       ",
-    "@.......@      ",
-    "@........@     ",
-    "@.........@    ",
-    "@..........@   ",
-    "@...........@  ",
-    "@............@ ",
-    "@......@@@@@@@@",
-    "@......@       ",
-    "@....@@.@      ",
-    "@...@ @.@      ",
-    "@..@   @.@     ",
-    "@.@    @.@     ",
-    "@@      @.@    ",
-    "@       @.@    ",
-    "         @.@   ",
-    "         @@@   ",
-];
-
 impl Mouse {
     pub fn new(initial_pos: (usize, usize), screen_size: (usize, usize)) -> Self {
         Self {
@@ -86,23 +57,7 @@ impl Mouse {
 
     pub fn draw_mouse<T: FrameBufferWriter>(&self, buffer: &mut T) {
         let (x, y) = self.current_pos;
-        for dy in 0..MOUSE_CURSOR_HEIGHT {
-            for dx in 0..MOUSE_CURSOR_WIDTH {
-                let c = MOUSE_CURSOR[dy].as_bytes()[dx];
-                let pixels_per_scan_line = buffer.get_pixels_per_scan_line();
-                let vertical_resolution = buffer.get_vertical_resolution();
-                if x + dx >= pixels_per_scan_line || y + dy >= vertical_resolution {
-                    continue;
-                }
-                if c == b'@' {
-                    let black = &PixelColor::new(0, 0, 0);
-                    buffer.write_pixel(x + dx, y + dy, black);
-                } else if c == b'.' {
-                    let white = &PixelColor::new(255, 255, 255);
-                    buffer.write_pixel(x + dx, y + dy, white);
-                }
-            }
-        }
+        draw_cursor(buffer, x as isize, y as isize);
     }
 }
 
@@ -0,0 +1,16 @@
+pub use mikanos_rs_kernel_params::{KernelParams, LogLevel};
+
+static PARAMS: spin::Once<KernelParams> = spin::Once::new();
+
+pub fn init(cmdline: &str) -> &'static KernelParams {
+    PARAMS.call_once(|| {
+        KernelParams::parse(cmdline, |err| {
+            crate::serial_println!("cmdline: {}", err);
+        })
+    })
+}
+
+/// Returns the boot parameters. Defaults are used if called before `init`.
+pub fn get() -> &'static KernelParams {
+    PARAMS.call_once(KernelParams::default)
+}
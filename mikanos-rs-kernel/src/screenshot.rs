@@ -0,0 +1,96 @@
+//! Dumps the screen over COM1 so that a host can pick it up from QEMU's `-serial stdio` output.
+//!
+//! The image is a binary PPM, base64-encoded in lines of 76 characters between `BEGIN_MARKER` and
+//! `END_MARKER` lines. Each of those lines starts with `DATA_PREFIX`, because other output may be
+//! interleaved. `tools/extract-screenshots.py` turns a serial log back into image files.
+use mikanos_rs_frame_buffer::{FrameBufferWriter, write_ppm};
+
+const BEGIN_MARKER: &str = "-----BEGIN MIKANOS SCREENSHOT-----";
+const END_MARKER: &str = "-----END MIKANOS SCREENSHOT-----";
+const DATA_PREFIX: u8 = b'|';
+const LINE_LENGTH: usize = 76;
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Base64 encoder that writes complete lines to the serial port.
+struct Base64Writer {
+    pending: [u8; 3],
+    num_pending: usize,
+    line: [u8; LINE_LENGTH],
+    column: usize,
+}
+
+impl Base64Writer {
+    fn write(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.pending[self.num_pending] = byte;
+            self.num_pending += 1;
+            if self.num_pending == 3 {
+                self.flush_group();
+            }
+        }
+    }
+
+    fn flush_group(&mut self) {
+        let [a, b, c] = self.pending;
+        let indices = [
+            a >> 2,
+            ((a & 0x03) << 4) | (b >> 4),
+            ((b & 0x0f) << 2) | (c >> 6),
+            c & 0x3f,
+        ];
+        for (i, &index) in indices.iter().enumerate() {
+            // A partial group of n bytes becomes n + 1 characters and padding.
+            let ch = if i <= self.num_pending {
+                BASE64_CHARS[index as usize]
+            } else {
+                b'='
+            };
+            self.line[self.column] = ch;
+            self.column += 1;
+            if self.column == LINE_LENGTH {
+                self.flush_line();
+            }
+        }
+        self.pending = [0; 3];
+        self.num_pending = 0;
+    }
+
+    /// Sends the line with interrupts disabled only for its duration, so that the dump doesn't
+    /// stall interrupt handling for seconds.
+    fn flush_line(&mut self) {
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            let mut serial = crate::serial::SERIAL1.lock();
+            serial.send(DATA_PREFIX);
+            for &ch in &self.line[..self.column] {
+                serial.send(ch);
+            }
+            serial.send(b'\n');
+        });
+        self.column = 0;
+    }
+
+    fn finish(mut self) {
+        if self.num_pending > 0 {
+            self.flush_group();
+        }
+        if self.column > 0 {
+            self.flush_line();
+        }
+    }
+}
+
+/// Writes the visible area of `buffer` to COM1.
+///
+/// The serial port is locked one line at a time, so other output may appear between the lines.
+pub fn dump<W: FrameBufferWriter + ?Sized>(buffer: &W) {
+    crate::serial_println!("{}", BEGIN_MARKER);
+    let mut encoder = Base64Writer {
+        pending: [0; 3],
+        num_pending: 0,
+        line: [0; LINE_LENGTH],
+        column: 0,
+    };
+    write_ppm(buffer, |bytes| encoder.write(bytes));
+    encoder.finish();
+    crate::serial_println!("{}", END_MARKER);
+}
@@ -1,15 +1,58 @@
+use alloc::string::String;
 use lazy_static::lazy_static;
 use spin::Mutex;
 use uart_16550::SerialPort;
 
+const COM1: u16 = 0x3f8;
+
 lazy_static! {
     pub static ref SERIAL1: Mutex<SerialPort> = {
-        let mut serial_port = unsafe { SerialPort::new(0x3f8) };
+        let mut serial_port = unsafe { SerialPort::new(COM1) };
         serial_port.init();
         Mutex::new(serial_port)
     };
 }
 
+/// Line status register bit set when a received byte is waiting.
+const LINE_STATUS_DATA_READY: u8 = 0x01;
+
+/// Returns the next byte received from the host, if any, without blocking.
+pub fn try_receive() -> Option<u8> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut serial = SERIAL1.lock();
+        let mut line_status = x86_64::instructions::port::PortReadOnly::<u8>::new(COM1 + 5);
+        let ready = unsafe { line_status.read() } & LINE_STATUS_DATA_READY != 0;
+        ready.then(|| serial.receive())
+    })
+}
+
+/// Collects host input into lines, for debug commands typed into QEMU's `-serial stdio`.
+pub struct LineReader {
+    line: String,
+}
+
+impl LineReader {
+    pub const fn new() -> Self {
+        Self {
+            line: String::new(),
+        }
+    }
+
+    /// Reads what the host has sent so far. Returns a line once it is complete.
+    pub fn poll(&mut self) -> Option<String> {
+        while let Some(byte) = try_receive() {
+            match byte {
+                b'\r' | b'\n' if !self.line.is_empty() => {
+                    return Some(core::mem::take(&mut self.line));
+                }
+                b'\r' | b'\n' => {}
+                _ => self.line.push(byte as char),
+            }
+        }
+        None
+    }
+}
+
 pub fn _print(args: ::core::fmt::Arguments) {
     use core::fmt::Write;
     x86_64::instructions::interrupts::without_interrupts(|| {
@@ -24,7 +67,7 @@ pub fn _print(args: ::core::fmt::Arguments) {
 #[macro_export]
 macro_rules! serial_print {
     ($($arg:tt)*) => {
-        $crate::serial::_print(format_args!($($arg)*));
+        $crate::serial::_print(format_args!($($arg)*))
     };
 }
 
@@ -0,0 +1,143 @@
+use core::{fmt, ops::Range};
+
+use mikanos_rs_boot_info::PhysicalRange;
+
+// ref: https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.symtab.html
+const SYM_SIZE: usize = 24;
+const STT_FUNC: u8 = 2;
+
+struct SymbolTable {
+    symtab: &'static [u8],
+    strtab: &'static [u8],
+    load_bias: u64,
+}
+
+static SYMBOLS: spin::Once<SymbolTable> = spin::Once::new();
+/// Address range of the kernel main stack, the only stack `print_backtrace` walks.
+static KERNEL_STACK: spin::Once<Range<u64>> = spin::Once::new();
+
+unsafe fn as_slice(range: PhysicalRange) -> &'static [u8] {
+    if range.is_empty() {
+        return &[];
+    }
+    unsafe { core::slice::from_raw_parts(range.start as *const u8, range.size as usize) }
+}
+
+pub fn init(symtab: PhysicalRange, strtab: PhysicalRange, load_bias: u64) {
+    SYMBOLS.call_once(|| SymbolTable {
+        symtab: unsafe { as_slice(symtab) },
+        strtab: unsafe { as_slice(strtab) },
+        load_bias,
+    });
+}
+
+/// Records the kernel main stack. Called before switching to it.
+pub fn set_kernel_stack(stack: Range<u64>) {
+    KERNEL_STACK.call_once(|| stack);
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+impl SymbolTable {
+    fn name(&self, offset: usize) -> &'static str {
+        let bytes = self.strtab.get(offset..).unwrap_or(&[]);
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        core::str::from_utf8(&bytes[..len]).unwrap_or("?")
+    }
+
+    /// Finds the function containing `addr` and returns its name and the offset into it.
+    fn lookup(&self, addr: u64) -> Option<(&'static str, u64)> {
+        let addr = addr.wrapping_sub(self.load_bias);
+        self.symtab
+            .chunks_exact(SYM_SIZE)
+            .filter(|sym| sym[4] & 0xf == STT_FUNC)
+            .find_map(|sym| {
+                let value = read_u64(&sym[8..16]);
+                let size = read_u64(&sym[16..24]);
+                if value <= addr && addr < value + size.max(1) {
+                    let name = u32::from_le_bytes(sym[0..4].try_into().unwrap()) as usize;
+                    Some((self.name(name), addr - value))
+                } else {
+                    None
+                }
+            })
+    }
+}
+
+/// Demangles legacy Rust symbols (`_ZN...E`), leaving anything else untouched.
+struct Demangle<'a>(&'a str);
+
+impl fmt::Display for Demangle<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(mut rest) = self.0.strip_prefix("_ZN") else {
+            return f.write_str(self.0);
+        };
+        let mut first = true;
+        while let Some(digits) = rest.find(|c: char| !c.is_ascii_digit()).filter(|&i| i > 0) {
+            let Ok(len) = rest[..digits].parse::<usize>() else {
+                break;
+            };
+            let Some(ident) = rest.get(digits..digits + len) else {
+                break;
+            };
+            rest = &rest[digits + len..];
+            // The last path segment is a hash like "h0123456789abcdef".
+            let is_hash = rest == "E"
+                && ident.len() == 17
+                && ident.starts_with('h')
+                && ident[1..].bytes().all(|b| b.is_ascii_hexdigit());
+            if is_hash {
+                break;
+            }
+            if !first {
+                f.write_str("::")?;
+            }
+            first = false;
+            f.write_str(ident)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats an address as `function+offset` when the symbol table knows it.
+pub struct Symbolized(pub u64);
+
+impl fmt::Display for Symbolized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match SYMBOLS.get().and_then(|symbols| symbols.lookup(self.0)) {
+            Some((name, offset)) => write!(f, "{:#x} {}+{:#x}", self.0, Demangle(name), offset),
+            None => write!(f, "{:#x}", self.0),
+        }
+    }
+}
+
+/// Prints the call stack by following the saved frame pointers.
+///
+/// The walk stops at the first frame that is not further up the kernel main stack than the previous
+/// one, so a corrupted `rbp` (or the loader's frames) is never dereferenced. Panics on other task
+/// stacks print no frames.
+pub fn print_backtrace() {
+    const MAX_DEPTH: usize = 32;
+    let stack = KERNEL_STACK.get().cloned().unwrap_or(0..0);
+    let mut rbp: u64;
+    unsafe { core::arch::asm!("mov {}, rbp", out(reg) rbp) };
+    crate::serial_println!("Backtrace:");
+    let mut prev_rbp = 0;
+    for depth in 0..MAX_DEPTH {
+        // A frame holds the saved rbp and the return address.
+        let in_stack =
+            stack.start <= rbp && rbp.checked_add(16).is_some_and(|end| end <= stack.end);
+        if !in_stack || rbp % 8 != 0 || rbp <= prev_rbp {
+            break;
+        }
+        let return_addr = unsafe { *((rbp + 8) as *const u64) };
+        if return_addr == 0 {
+            break;
+        }
+        crate::serial_println!("  #{}: {}", depth, Symbolized(return_addr));
+        prev_rbp = rbp;
+        rbp = unsafe { *(rbp as *const u64) };
+    }
+}
@@ -4,7 +4,39 @@ const INITIAL_COUNT: *mut u32 = 0xfee00380u64 as *mut u32;
 const CURRENT_COUNT: *mut u32 = 0xfee00390u64 as *mut u32;
 const DIVIDE_CONFIG: *mut u32 = 0xfee003e0u64 as *mut u32;
 
+static INTERVAL_COUNT: core::sync::atomic::AtomicU32 =
+    core::sync::atomic::AtomicU32::new(MAX_COUNT);
+
+/// Measures how far the local APIC timer counts down in 100 milliseconds.
+fn measure_count_per_100ms() -> Option<u32> {
+    unsafe {
+        core::ptr::write_volatile(DIVIDE_CONFIG, 0b1011); // divide 1:1
+        core::ptr::write_volatile(LVT_TIMER, (0b001 << 16) | 32); // masked, one-shot
+        core::ptr::write_volatile(INITIAL_COUNT, u32::MAX);
+    }
+    let calibrated = crate::acpi::wait_milliseconds(100);
+    let elapsed = u32::MAX - unsafe { core::ptr::read_volatile(CURRENT_COUNT) };
+    unsafe { core::ptr::write_volatile(INITIAL_COUNT, 0) };
+    calibrated.then_some(elapsed)
+}
+
+/// Starts the periodic timer interrupt, `timer_hz` times a second if the boot parameter is given.
+///
+/// Calibration uses the ACPI PM timer, so `acpi::init` must have run. Without `timer_hz` or the PM
+/// timer, the local APIC timer fires every `MAX_COUNT` bus clocks.
 pub unsafe fn init_local_apic_timer() {
+    if let Some(hz) = crate::params::get().timer_hz {
+        match measure_count_per_100ms() {
+            Some(count) => {
+                let interval = (count as u64 * 10 / hz as u64).clamp(1, u32::MAX as u64) as u32;
+                INTERVAL_COUNT.store(interval, core::sync::atomic::Ordering::Relaxed);
+                crate::serial_println!("Local APIC timer: {} Hz (count {})", hz, interval);
+            }
+            None => crate::serial_println!(
+                "Cannot calibrate the local APIC timer without the ACPI PM timer."
+            ),
+        }
+    }
     unsafe {
         core::ptr::write_volatile(DIVIDE_CONFIG, 0b1011); // divide 1:1
         core::ptr::write_volatile(
@@ -17,7 +49,10 @@ pub unsafe fn init_local_apic_timer() {
 
 fn start_local_apic_timer() {
     unsafe {
-        core::ptr::write_volatile(INITIAL_COUNT, MAX_COUNT);
+        core::ptr::write_volatile(
+            INITIAL_COUNT,
+            INTERVAL_COUNT.load(core::sync::atomic::Ordering::Relaxed),
+        );
     }
 }
 
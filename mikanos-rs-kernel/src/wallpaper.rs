@@ -0,0 +1,50 @@
+use mikanos_rs_frame_buffer::{FrameBufferInfo, MemoryBuffer, decode_image, image_size};
+
+/// Image built into the kernel with `MIKANOS_WALLPAPER=<path> cargo build`. See `build.rs`.
+#[cfg(embedded_wallpaper)]
+const EMBEDDED: Option<&[u8]> = Some(include_bytes!(env!("MIKANOS_WALLPAPER_PATH")));
+#[cfg(not(embedded_wallpaper))]
+const EMBEDDED: Option<&[u8]> = None;
+
+/// Decodes the BMP or QOI image at `path` in the initrd, falling back to the embedded one.
+///
+/// Images larger than the screen are refused before decoding, since a huge one would exhaust the
+/// heap.
+pub fn load(path: Option<&str>, frame_buffer: &FrameBufferInfo) -> Option<MemoryBuffer> {
+    let data = match path.map(|path| (path, crate::initrd::find_file(path))) {
+        Some((_, Some(data))) => data,
+        Some((path, None)) => {
+            crate::serial_println!("wallpaper: {} is not in the initrd", path);
+            EMBEDDED?
+        }
+        None => EMBEDDED?,
+    };
+    let screen = (
+        frame_buffer.get_horizontal_resolution(),
+        frame_buffer.get_vertical_resolution(),
+    );
+    match image_size(data) {
+        Ok((width, height)) if width > screen.0 || height > screen.1 => {
+            crate::serial_println!(
+                "wallpaper: {}x{} is larger than the {}x{} screen",
+                width,
+                height,
+                screen.0,
+                screen.1
+            );
+            return None;
+        }
+        Ok(_) => {}
+        Err(err) => {
+            crate::serial_println!("wallpaper: {}", err);
+            return None;
+        }
+    }
+    match decode_image(data, frame_buffer.get_pixel_layout()) {
+        Ok(image) => Some(image),
+        Err(err) => {
+            crate::serial_println!("wallpaper: {}", err);
+            None
+        }
+    }
+}
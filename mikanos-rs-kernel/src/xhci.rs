@@ -43,6 +43,7 @@ impl ErrorCode {
     }
 }
 type MouseObserverType = extern "C" fn(buttons: u8, displacement_x: i8, displacement_y: i8);
+type KeyboardObserverType = extern "C" fn(modifier: u8, keycode: u8, press: bool);
 
 unsafe extern "C" {
     fn create_xhci_controller(mmmio_base: u64) -> *mut Controller;
@@ -52,7 +53,8 @@ unsafe extern "C" {
     fn process_xhci_event(xhc: &mut Controller) -> ErrorCode;
     fn xhci_event_ring_is_empty(xhc: &mut Controller) -> bool;
     fn set_default_mouse_observer(observer: MouseObserverType);
-    fn set_default_keyboard_observer();
+    fn set_default_keyboard_observer(observer: KeyboardObserverType);
+    fn mikanos_rs_set_log_level(level: crate::params::LogLevel);
 }
 
 // Opaque type
@@ -104,10 +106,15 @@ pub fn get_xhc() -> &'static spin::Mutex<&'static mut Controller> {
     XHC.get().unwrap()
 }
 
+/// Sets the verbosity of the USB driver's logger.
+pub fn set_log_level(level: crate::params::LogLevel) {
+    unsafe { mikanos_rs_set_log_level(level) };
+}
+
 pub fn initialize_mouse() {
     unsafe { set_default_mouse_observer(crate::mouse::observer) };
 }
 
 pub fn initialize_keyboard() {
-    unsafe { set_default_keyboard_observer() };
+    unsafe { set_default_keyboard_observer(crate::keyboard::observer) };
 }
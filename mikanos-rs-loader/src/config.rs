@@ -0,0 +1,12 @@
+use log::warn;
+
+pub use mikanos_rs_boot_config::BootConfig;
+
+pub const CONFIG_PATH: &str = "\\mikanos\\boot.cfg";
+
+/// Parses the contents of `CONFIG_PATH`. Unknown keys and malformed values are logged and ignored.
+pub fn parse(text: &str) -> BootConfig {
+    BootConfig::parse(text, |lineno, err| {
+        warn!("{}:{}: {}", CONFIG_PATH, lineno, err)
+    })
+}
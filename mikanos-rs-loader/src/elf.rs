@@ -0,0 +1,251 @@
+use core::{fmt, slice};
+
+use goblin::elf;
+use log::{info, warn};
+use uefi::boot;
+use uefi::mem::memory_map::{MemoryMap, MemoryType};
+use uefi::proto::rng::Rng;
+
+use mikanos_rs_boot_info::{KERNEL_SYMBOLS_MEMORY_TYPE, MAX_LOAD_ADDRESS, PhysicalRange};
+use mikanos_rs_elf::ElfError;
+
+const PAGE_SIZE: u64 = 0x1000;
+
+pub struct LoadedKernel {
+    pub entry: u64,
+    pub image: PhysicalRange,
+    /// Added to every link-time address of the kernel. Always 0 for non-relocatable kernels.
+    pub load_bias: u64,
+    /// Copies of `.symtab` and its string table, or empty ranges if the kernel is stripped.
+    pub symtab: PhysicalRange,
+    pub strtab: PhysicalRange,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Read(uefi::Error),
+    InvalidElf(ElfError),
+    NoMemory,
+    Allocation(uefi::Error),
+    /// A relocation at this link-time address would write outside the image or produce an
+    /// unrepresentable address.
+    BadRelocation(u64),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(err) => write!(f, "cannot read the file: {:?}", err.status()),
+            Self::InvalidElf(err) => write!(f, "invalid kernel image: {}", err),
+            Self::NoMemory => write!(f, "no memory region can hold the kernel image"),
+            Self::Allocation(err) => {
+                write!(f, "cannot allocate the kernel image: {:?}", err.status())
+            }
+            Self::BadRelocation(offset) => write!(f, "invalid relocation at {:#x}", offset),
+        }
+    }
+}
+
+fn random_u64() -> u64 {
+    let rng = boot::get_handle_for_protocol::<Rng>().and_then(boot::open_protocol_exclusive::<Rng>);
+    if let Ok(mut rng) = rng {
+        let mut buf = [0u8; 8];
+        if rng.get_rng(None, &mut buf).is_ok() {
+            return u64::from_le_bytes(buf);
+        }
+    }
+    warn!("EFI_RNG_PROTOCOL is not available, falling back to the time stamp counter.");
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// Picks an `align`-aligned base address in conventional memory that can hold `size` bytes.
+///
+/// With `randomize`, the base is chosen uniformly among all candidates. Otherwise the lowest one is
+/// used.
+fn find_base(size: u64, align: u64, randomize: bool) -> Option<u64> {
+    let memory_map = boot::memory_map(MemoryType::LOADER_DATA).ok()?;
+    // Each descriptor contributes the aligned start addresses at which the image fits entirely.
+    let slots = |desc: &uefi::mem::memory_map::MemoryDescriptor| {
+        let start = desc.phys_start.next_multiple_of(align);
+        let end = u64::min(
+            desc.phys_start + desc.page_count * PAGE_SIZE,
+            MAX_LOAD_ADDRESS,
+        );
+        if desc.ty != MemoryType::CONVENTIONAL || start == 0 || start + size > end {
+            return (start, 0);
+        }
+        (start, (end - size - start) / align + 1)
+    };
+    let total: u64 = memory_map.entries().map(|desc| slots(desc).1).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut index = if randomize { random_u64() % total } else { 0 };
+    for desc in memory_map.entries() {
+        let (start, count) = slots(desc);
+        if index < count {
+            return Some(start + index * align);
+        }
+        index -= count;
+    }
+    None
+}
+
+/// Copies `data` into freshly allocated pages that the kernel keeps after boot.
+fn copy_to_kernel_memory(data: &[u8]) -> Result<PhysicalRange, LoadError> {
+    if data.is_empty() {
+        return Ok(PhysicalRange::empty());
+    }
+    let page_cnt = (data.len() as u64).div_ceil(PAGE_SIZE) as usize;
+    let base = boot::allocate_pages(
+        boot::AllocateType::MaxAddress(MAX_LOAD_ADDRESS - 1),
+        KERNEL_SYMBOLS_MEMORY_TYPE,
+        page_cnt,
+    )
+    .map_err(LoadError::Allocation)?;
+    let dest = unsafe { slice::from_raw_parts_mut(base.as_ptr(), data.len()) };
+    dest.copy_from_slice(data);
+    Ok(PhysicalRange::new(base.as_ptr() as u64, data.len() as u64))
+}
+
+fn section_data<'a>(elf_data: &'a [u8], shdr: &elf::SectionHeader) -> Option<&'a [u8]> {
+    let start = usize::try_from(shdr.sh_offset).ok()?;
+    let end = start.checked_add(usize::try_from(shdr.sh_size).ok()?)?;
+    elf_data.get(start..end)
+}
+
+/// Copies the symbol table and its string table so that the kernel can symbolize addresses.
+fn load_symbols(
+    elf_data: &[u8],
+    prog: &elf::Elf,
+) -> Result<(PhysicalRange, PhysicalRange), LoadError> {
+    let symtab = prog
+        .section_headers
+        .iter()
+        .find(|shdr| shdr.sh_type == elf::section_header::SHT_SYMTAB);
+    let Some(symtab) = symtab else {
+        warn!("The kernel has no symbol table, backtraces won't be symbolized.");
+        return Ok((PhysicalRange::empty(), PhysicalRange::empty()));
+    };
+    let strtab = prog.section_headers.get(symtab.sh_link as usize);
+    let data = strtab.and_then(|strtab| {
+        Some((
+            section_data(elf_data, symtab)?,
+            section_data(elf_data, strtab)?,
+        ))
+    });
+    let Some((symtab_data, strtab_data)) = data else {
+        warn!("The kernel symbol table is broken, ignoring it.");
+        return Ok((PhysicalRange::empty(), PhysicalRange::empty()));
+    };
+    Ok((
+        copy_to_kernel_memory(symtab_data)?,
+        copy_to_kernel_memory(strtab_data)?,
+    ))
+}
+
+/// Applies relocations of an image validated by `mikanos_rs_elf::validate`, so all of them are
+/// `R_X86_64_NONE` or `R_X86_64_RELATIVE`. The image is loaded at `base`, where its link-time
+/// address `addr_start` ends up.
+///
+/// Each target is checked again before writing, since a bad target would let the image overwrite
+/// any physical memory, including the loader itself.
+fn apply_relocations(prog: &elf::Elf, base: u64, addr_start: u64) -> Result<(), LoadError> {
+    let relocs = prog
+        .dynrelas
+        .iter()
+        .chain(prog.dynrels.iter())
+        .chain(prog.pltrelocs.iter());
+    for reloc in relocs {
+        if reloc.r_type != elf::reloc::R_X86_64_RELATIVE {
+            continue;
+        }
+        if !mikanos_rs_elf::contains_range(prog, reloc.r_offset, 8) {
+            return Err(LoadError::BadRelocation(reloc.r_offset));
+        }
+        // Every loadable segment starts at or after `addr_start`.
+        let target = (base + (reloc.r_offset - addr_start)) as *mut u64;
+        // REL entries keep the addend in the relocated word itself.
+        let addend = reloc
+            .r_addend
+            .unwrap_or_else(|| unsafe { target.read_unaligned() } as i64);
+        let value = u64::try_from(base as i128 + addend as i128 - addr_start as i128)
+            .map_err(|_| LoadError::BadRelocation(reloc.r_offset))?;
+        unsafe { target.write_unaligned(value) };
+    }
+    Ok(())
+}
+
+pub fn load_elf(elf_data: &[u8], kaslr: bool) -> Result<LoadedKernel, LoadError> {
+    let prog = mikanos_rs_elf::validate(elf_data).map_err(LoadError::InvalidElf)?;
+    let relocatable = prog.header.e_type == elf::header::ET_DYN;
+
+    // Calculate address range
+    let mut addr_start = u64::MAX;
+    let mut addr_end = 0;
+    let mut align = PAGE_SIZE;
+    for phdr in prog.program_headers.iter() {
+        if phdr.p_type != elf::program_header::PT_LOAD {
+            continue;
+        }
+        addr_start = u64::min(addr_start, phdr.p_vaddr);
+        addr_end = u64::max(addr_end, phdr.p_vaddr + phdr.p_memsz);
+        align = u64::max(align, phdr.p_align);
+    }
+    addr_start &= !(PAGE_SIZE - 1);
+
+    // Allocate memory for kernel image
+    let memsz = addr_end - addr_start;
+    let page_cnt = memsz.div_ceil(PAGE_SIZE) as usize;
+    let base = if relocatable {
+        find_base(memsz, align, kaslr).ok_or(LoadError::NoMemory)?
+    } else {
+        if kaslr {
+            warn!("The kernel is not relocatable, ignoring kaslr.");
+        }
+        addr_start
+    };
+    info!(
+        "Try allocating {:x} pages starting at {:x}.",
+        page_cnt, base
+    );
+    boot::allocate_pages(
+        boot::AllocateType::Address(base),
+        boot::MemoryType::LOADER_DATA,
+        page_cnt,
+    )
+    .map_err(LoadError::Allocation)?;
+    let load_bias = base.wrapping_sub(addr_start);
+
+    // Copy loadable segments
+    for phdr in prog.program_headers.iter() {
+        if phdr.p_type != elf::program_header::PT_LOAD {
+            continue;
+        }
+        let dest = unsafe {
+            slice::from_raw_parts_mut(
+                phdr.p_vaddr.wrapping_add(load_bias) as *mut u8,
+                phdr.p_memsz as usize,
+            )
+        };
+        dest[..phdr.p_filesz as usize].copy_from_slice(
+            &elf_data[phdr.p_offset as usize..(phdr.p_offset + phdr.p_filesz) as usize],
+        );
+        dest[phdr.p_filesz as usize..].fill(0);
+    }
+
+    if relocatable {
+        apply_relocations(&prog, base, addr_start)?;
+        info!("Relocated kernel to {:#x}.", base);
+    }
+
+    let (symtab, strtab) = load_symbols(elf_data, &prog)?;
+
+    Ok(LoadedKernel {
+        entry: prog.entry.wrapping_add(load_bias),
+        image: PhysicalRange::new(base, page_cnt as u64 * PAGE_SIZE),
+        load_bias,
+        symtab,
+        strtab,
+    })
+}
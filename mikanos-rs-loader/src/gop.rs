@@ -0,0 +1,117 @@
+use log::{info, warn};
+use uefi::prelude::*;
+use uefi::proto::console::gop::{GraphicsOutput, Mode, PixelFormat};
+
+pub fn open_gop() -> uefi::Result<boot::ScopedProtocol<GraphicsOutput>> {
+    let gop_handle = boot::get_handle_for_protocol::<GraphicsOutput>()?;
+
+    let gop = unsafe {
+        boot::open_protocol::<GraphicsOutput>(
+            boot::OpenProtocolParams {
+                handle: gop_handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            // Don't open in exclusive mode.
+            // That would break the connection between stdout and the video console.
+            // ref: https://github.com/rust-osdev/uefi-rs/issues/524
+            boot::OpenProtocolAttributes::GetProtocol,
+        )?
+    };
+    Ok(gop)
+}
+
+/// BltOnly modes have no linear frame buffer for the kernel to draw into.
+fn is_supported(mode: &Mode) -> bool {
+    mode.info().pixel_format() != PixelFormat::BltOnly
+}
+
+fn area(mode: &Mode) -> usize {
+    let (horizontal, vertical) = mode.info().resolution();
+    horizontal * vertical
+}
+
+pub fn log_modes(gop: &GraphicsOutput) {
+    for (i, mode) in gop.modes().enumerate() {
+        let (horizontal, vertical) = mode.info().resolution();
+        info!(
+            "Mode {}: {}x{}, {:?}, {} pixels/line",
+            i,
+            horizontal,
+            vertical,
+            mode.info().pixel_format(),
+            mode.info().stride()
+        );
+    }
+}
+
+/// Picks the mode closest to the requested resolution and pixel format.
+///
+/// Without a requested resolution, the largest supported mode wins. Otherwise an exact resolution
+/// match is preferred, then the largest mode fitting in the requested resolution. A mode with the
+/// requested pixel format wins over an otherwise equal one.
+pub fn select_mode(
+    gop: &GraphicsOutput,
+    resolution: Option<(usize, usize)>,
+    pixel_format: Option<PixelFormat>,
+) -> Option<Mode> {
+    let score = |mode: &Mode| {
+        let (horizontal, vertical) = mode.info().resolution();
+        let (exact, fits) = match resolution {
+            Some((h, v)) => (
+                horizontal == h && vertical == v,
+                horizontal <= h && vertical <= v,
+            ),
+            None => (false, true),
+        };
+        let format_matches = pixel_format == Some(mode.info().pixel_format());
+        (exact, fits, area(mode), format_matches)
+    };
+    gop.modes().filter(is_supported).max_by_key(score)
+}
+
+pub fn set_mode(
+    gop: &mut GraphicsOutput,
+    resolution: Option<(usize, usize)>,
+    pixel_format: Option<PixelFormat>,
+) {
+    log_modes(gop);
+    let Some(mode) = select_mode(gop, resolution, pixel_format) else {
+        warn!("No graphics mode with a frame buffer found, keeping the current mode.");
+        return;
+    };
+    if let Some((h, v)) = resolution {
+        if mode.info().resolution() != (h, v) {
+            warn!("Resolution {}x{} is not supported.", h, v);
+        }
+    }
+    let (horizontal, vertical) = mode.info().resolution();
+    info!(
+        "Selected mode: {}x{}, {:?}",
+        horizontal,
+        vertical,
+        mode.info().pixel_format()
+    );
+    if gop.current_mode_info() != *mode.info() {
+        gop.set_mode(&mode).expect("Failed to set graphics mode.");
+    }
+}
+
+pub fn log_gop_info(gop: &mut GraphicsOutput) {
+    let (horizontal, vertical) = gop.current_mode_info().resolution();
+    let pixel_format = gop.current_mode_info().pixel_format();
+    let pixels_per_scanline = gop.current_mode_info().stride();
+    info!(
+        "Resolution: {}x{}, Pixel Format: {:?}, {} pixels/line",
+        horizontal, vertical, pixel_format, pixels_per_scanline
+    );
+
+    let frame_buffer_base = gop.frame_buffer().as_mut_ptr() as usize;
+    let frame_buffer_size = gop.frame_buffer().size();
+    info!(
+        "Frame Buffer: {:#x} - {:#x}, Size: {} bytes",
+        frame_buffer_base,
+        frame_buffer_base + frame_buffer_size,
+        frame_buffer_size
+    );
+}
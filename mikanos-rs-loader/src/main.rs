@@ -2,20 +2,30 @@
 #![no_main]
 
 extern crate alloc;
-use alloc::{vec, vec::Vec};
+use alloc::{string::String, vec, vec::Vec};
+
+mod config;
+mod elf;
+mod gop;
+mod memmap;
+mod menu;
 
 use core::slice;
 
-use goblin::elf;
-use log::info;
-use uefi::mem::memory_map::MemoryMapOwned;
+use log::{error, info, warn};
+use uefi::CString16;
+use uefi::mem::memory_map::{MemoryMapOwned, MemoryType};
 use uefi::prelude::*;
-use uefi::proto::console::gop::GraphicsOutput;
 use uefi::proto::loaded_image::LoadedImage;
 use uefi::proto::media::file::{Directory, File, FileAttribute, FileInfo, FileMode, RegularFile};
 use uefi::proto::media::fs::SimpleFileSystem;
+use uefi::table::cfg::{ACPI_GUID, ACPI2_GUID};
+
+use mikanos_rs_boot_info::{BootInfo, INITRD_MEMORY_TYPE, MAX_LOAD_ADDRESS, PhysicalRange};
+use mikanos_rs_frame_buffer::FrameBufferInfo;
 
-use mikanos_rs_frame_buffer::FrameBuffer;
+use config::{BootConfig, CONFIG_PATH};
+use elf::{LoadError, LoadedKernel};
 
 fn open_root_dir() -> uefi::Result<Directory> {
     let loaded_image = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle())?;
@@ -24,104 +34,127 @@ fn open_root_dir() -> uefi::Result<Directory> {
     fs.open_volume()
 }
 
+/// Fills `buf` from `file`. `File::read` may return fewer bytes than asked for.
+fn read_exact(file: &mut RegularFile, buf: &mut [u8]) -> uefi::Result<()> {
+    let mut done = 0;
+    while done < buf.len() {
+        match file.read(&mut buf[done..])? {
+            0 => return Err(uefi::Status::END_OF_FILE.into()),
+            n => done += n,
+        }
+    }
+    Ok(())
+}
+
 fn read_file(file: &mut RegularFile) -> uefi::Result<Vec<u8>> {
     let info = file.get_boxed_info::<FileInfo>()?;
     let size = info.file_size() as usize;
     let mut buf = vec![0; size];
-    file.read(&mut buf)?;
+    read_exact(file, &mut buf)?;
     Ok(buf)
 }
 
-fn load_elf(elf_data: &[u8]) -> elf::Elf {
-    let prog = elf::Elf::parse(elf_data).unwrap();
-
-    // Calculate address range
-    let mut addr_start = usize::MAX;
-    let mut addr_end = 0;
-    for phdr in prog.program_headers.iter() {
-        if phdr.p_type != elf::program_header::PT_LOAD {
-            continue;
+fn read_config(root_dir: &mut Directory) -> BootConfig {
+    let path = CString16::try_from(CONFIG_PATH).unwrap();
+    let file = match root_dir.open(&path, FileMode::Read, FileAttribute::empty()) {
+        Ok(file) => file,
+        Err(err) => {
+            info!(
+                "No boot config at {} ({:?}), using defaults.",
+                CONFIG_PATH,
+                err.status()
+            );
+            return BootConfig::default();
         }
-        addr_start = usize::min(addr_start, phdr.p_vaddr as usize);
-        addr_end = usize::max(addr_end, (phdr.p_vaddr + phdr.p_memsz) as usize);
-    }
-
-    // Allocate memory for kernel image
-    let memsz = addr_end - addr_start;
-    let page_size = 0x1000;
-    let page_cnt = (memsz + page_size - 1) / page_size;
-    info!(
-        "Try allocating {:x} pages starting at {:x}.",
-        page_cnt, addr_start
-    );
-    boot::allocate_pages(
-        boot::AllocateType::Address(addr_start as u64),
-        boot::MemoryType::LOADER_DATA,
-        page_cnt,
-    )
-    .unwrap();
-
-    // Copy loadable segments
-    for phdr in prog.program_headers.iter() {
-        if phdr.p_type != elf::program_header::PT_LOAD {
-            continue;
+    };
+    let Some(mut file) = file.into_regular_file() else {
+        warn!("{} is not a regular file, using defaults.", CONFIG_PATH);
+        return BootConfig::default();
+    };
+    let buf = read_file(&mut file).expect("Failed to read boot config.");
+    match core::str::from_utf8(&buf) {
+        Ok(text) => config::parse(text),
+        Err(_) => {
+            warn!("{} is not valid UTF-8, using defaults.", CONFIG_PATH);
+            BootConfig::default()
         }
-        let dest =
-            unsafe { slice::from_raw_parts_mut(phdr.p_vaddr as *mut u8, phdr.p_memsz as usize) };
-        dest[..phdr.p_filesz as usize].copy_from_slice(
-            &elf_data[phdr.p_offset as usize..(phdr.p_offset + phdr.p_filesz) as usize],
-        );
-        dest[phdr.p_filesz as usize..].fill(0);
     }
+}
 
-    prog
+fn load_initrd(root_dir: &mut Directory, path: &str) -> uefi::Result<PhysicalRange> {
+    let path = CString16::try_from(path).map_err(|_| uefi::Status::INVALID_PARAMETER)?;
+    let mut file = root_dir
+        .open(&path, FileMode::Read, FileAttribute::empty())?
+        .into_regular_file()
+        .ok_or(uefi::Status::INVALID_PARAMETER)?;
+    let size = file.get_boxed_info::<FileInfo>()?.file_size() as usize;
+    let page_cnt = size.div_ceil(boot::PAGE_SIZE);
+    let base = boot::allocate_pages(
+        boot::AllocateType::MaxAddress(MAX_LOAD_ADDRESS - 1),
+        INITRD_MEMORY_TYPE,
+        page_cnt,
+    )?;
+    let buf = unsafe { slice::from_raw_parts_mut(base.as_ptr(), size) };
+    read_exact(&mut file, buf)?;
+    Ok(PhysicalRange::new(base.as_ptr() as u64, size as u64))
 }
 
-type EntryPoint = extern "sysv64" fn(&FrameBuffer, &MemoryMapOwned);
-fn load_kernel(kernel_file: &mut RegularFile) -> uefi::Result<EntryPoint> {
-    let buf = read_file(kernel_file)?;
-    info!("Read kernel file: size={}", buf.len());
-    let prog = load_elf(&buf);
-    let entry: EntryPoint = unsafe { core::mem::transmute(prog.entry) };
-    Ok(entry)
+/// Returns the kernel path and command line to boot, asking the user if a boot menu is configured.
+fn choose_kernel<'a>(root_dir: &mut Directory, config: &'a BootConfig) -> (String, &'a str) {
+    let Some(kernel_dir) = &config.kernel_dir else {
+        return (config.kernel_path.clone(), &config.cmdline);
+    };
+    let entries = match menu::find_kernels(root_dir, kernel_dir) {
+        Ok(entries) if !entries.is_empty() => entries,
+        Ok(_) => {
+            warn!("No kernels found in {}.", kernel_dir);
+            return (config.kernel_path.clone(), &config.cmdline);
+        }
+        Err(err) => {
+            warn!("Cannot read {}: {:?}", kernel_dir, err.status());
+            return (config.kernel_path.clone(), &config.cmdline);
+        }
+    };
+    let default = entries
+        .iter()
+        .position(|entry| entry.path.eq_ignore_ascii_case(&config.kernel_path))
+        .unwrap_or(0);
+    let selected = if config.timeout == 0 {
+        default
+    } else {
+        menu::select(&entries, default, config.timeout)
+    };
+    let entry = &entries[selected];
+    (entry.path.clone(), config.cmdline_for(&entry.name))
 }
 
-fn open_gop() -> uefi::Result<boot::ScopedProtocol<GraphicsOutput>> {
-    let gop_handle = boot::get_handle_for_protocol::<GraphicsOutput>()?;
-
-    let gop = unsafe {
-        boot::open_protocol::<GraphicsOutput>(
-            boot::OpenProtocolParams {
-                handle: gop_handle,
-                agent: boot::image_handle(),
-                controller: None,
-            },
-            // Don't open in exclusive mode.
-            // That would break the connection between stdout and the video console.
-            // ref: https://github.com/rust-osdev/uefi-rs/issues/524
-            boot::OpenProtocolAttributes::GetProtocol,
-        )?
+/// Returns the physical address of the ACPI RSDP, preferring the ACPI 2.0 one.
+fn find_acpi_rsdp() -> Option<u64> {
+    let find = |guid| {
+        system::with_config_table(|entries| {
+            entries
+                .iter()
+                .find(|entry| entry.guid == guid)
+                .map(|entry| entry.address as u64)
+        })
     };
-    Ok(gop)
+    find(ACPI2_GUID).or_else(|| {
+        let rsdp = find(ACPI_GUID)?;
+        warn!("ACPI 2.0 RSDP not found, using the ACPI 1.0 one.");
+        Some(rsdp)
+    })
 }
 
-fn log_gop_info(gop: &mut GraphicsOutput) {
-    let (horizontal, vertical) = gop.current_mode_info().resolution();
-    let pixel_format = gop.current_mode_info().pixel_format();
-    let pixels_per_scanline = gop.current_mode_info().stride();
-    info!(
-        "Resolution: {}x{}, Pixel Format: {:?}, {} pixels/line",
-        horizontal, vertical, pixel_format, pixels_per_scanline
-    );
-
-    let frame_buffer_base = gop.frame_buffer().as_mut_ptr() as usize;
-    let frame_buffer_size = gop.frame_buffer().size();
-    info!(
-        "Frame Buffer: {:#x} - {:#x}, Size: {} bytes",
-        frame_buffer_base,
-        frame_buffer_base + frame_buffer_size,
-        frame_buffer_size
-    );
+type EntryPoint = extern "sysv64" fn(&BootInfo);
+fn load_kernel(
+    kernel_file: &mut RegularFile,
+    kaslr: bool,
+) -> Result<(EntryPoint, LoadedKernel), LoadError> {
+    let buf = read_file(kernel_file).map_err(LoadError::Read)?;
+    info!("Read kernel file: size={}", buf.len());
+    let kernel = elf::load_elf(&buf, kaslr)?;
+    let entry: EntryPoint = unsafe { core::mem::transmute(kernel.entry) };
+    Ok((entry, kernel))
 }
 
 #[entry]
@@ -130,28 +163,84 @@ fn main() -> Status {
     info!("Hello, mikanos-rs!");
 
     let mut root_dir = open_root_dir().expect("Failed to open root directory.");
+    let config = read_config(&mut root_dir);
+    log::set_max_level(config.log_level);
+
+    let mut gop = gop::open_gop().expect("Failed to open gop.");
+    gop::set_mode(&mut gop, config.resolution, config.pixel_format);
+    let Some(frame_buffer) = FrameBufferInfo::new(&mut gop) else {
+        error!("The graphics mode only supports Blt() and has no frame buffer the kernel can use.");
+        boot::stall(10_000_000);
+        return Status::UNSUPPORTED;
+    };
+    gop::log_gop_info(&mut gop);
 
-    let mut gop = open_gop().expect("Failed to open gop.");
-    let frame_buffer = FrameBuffer::new(&mut gop);
-    log_gop_info(&mut gop);
-
+    let (kernel_path_str, cmdline) = choose_kernel(&mut root_dir, &config);
+    info!("Loading kernel from {}", kernel_path_str);
+    let kernel_path = CString16::try_from(kernel_path_str.as_str()).expect("Invalid kernel path.");
     let mut kernel_file = root_dir
-        .open(
-            cstr16!("\\kernel.elf"),
-            FileMode::Read,
-            FileAttribute::empty(),
-        )
+        .open(&kernel_path, FileMode::Read, FileAttribute::empty())
         .expect("Failed to open kernel file.")
         .into_regular_file()
         .unwrap();
-    let entry = load_kernel(&mut kernel_file).expect("Failed to load kernel");
+    let (entry, kernel) = match load_kernel(&mut kernel_file, config.kaslr) {
+        Ok(kernel) => kernel,
+        Err(err) => {
+            // The logger writes to the console, which the firmware also mirrors to serial.
+            error!("Failed to load kernel {}: {}", kernel_path_str, err);
+            boot::stall(10_000_000);
+            return Status::LOAD_ERROR;
+        }
+    };
     info!("entry: {:x}", entry as u64);
     info!("Successfully loaded kernel!");
 
+    let mut boot_info = BootInfo::new(frame_buffer);
+    boot_info.kernel_image = kernel.image;
+    boot_info.kernel_load_bias = kernel.load_bias;
+    boot_info.kernel_symtab = kernel.symtab;
+    boot_info.kernel_strtab = kernel.strtab;
+    if let Some(initrd_path) = &config.initrd_path {
+        let initrd = load_initrd(&mut root_dir, initrd_path).expect("Failed to load initrd.");
+        info!(
+            "Loaded initrd {}: {:#x} - {:#x}",
+            initrd_path,
+            initrd.start,
+            initrd.end()
+        );
+        boot_info.initrd = initrd;
+    }
+    match find_acpi_rsdp() {
+        Some(rsdp) => {
+            info!("ACPI RSDP: {:#x}", rsdp);
+            boot_info.acpi_rsdp = rsdp;
+        }
+        None => warn!("ACPI RSDP not found."),
+    }
+    let system_table = uefi::table::system_table_raw().expect("System table should exist.");
+    boot_info.efi_system_table = system_table.as_ptr() as u64;
+    boot_info.efi_runtime_services = unsafe { system_table.as_ref().runtime_services } as u64;
+    // `config` outlives the kernel call, so the kernel can read the command line in place.
+    boot_info.cmdline = PhysicalRange::new(cmdline.as_ptr() as u64, cmdline.len() as u64);
+
+    let memory_map = boot::memory_map(MemoryType::LOADER_DATA).expect("Failed to get memory map.");
+    memmap::log_summary(&memory_map);
+    if let Some(memmap_path) = &config.memmap_path {
+        match memmap::save(&mut root_dir, memmap_path, &memory_map) {
+            Ok(()) => info!("Saved memory map to {}", memmap_path),
+            Err(err) => warn!(
+                "Failed to save memory map to {}: {:?}",
+                memmap_path,
+                err.status()
+            ),
+        }
+    }
+
     info!("Exiting boot services...");
     // Is it correct to use LOADER_DATA type here?
     let memory_map = unsafe { boot::exit_boot_services(boot::MemoryType::LOADER_DATA) };
-    entry(&frame_buffer, &memory_map);
+    boot_info.memory_map = &memory_map as *const MemoryMapOwned;
+    entry(&boot_info);
 
     info!("All done.");
     boot::stall(10_000_000);
@@ -0,0 +1,64 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use core::fmt::Write;
+
+use log::info;
+use uefi::CString16;
+use uefi::mem::memory_map::{MemoryMap, MemoryType};
+use uefi::proto::media::file::{Directory, File, FileAttribute, FileMode};
+
+/// Formats the memory map as CSV, in the same columns as the original MikanOS `memmap` file.
+fn to_csv(memory_map: &impl MemoryMap) -> String {
+    let mut csv =
+        String::from("Index, Type, Type(name), PhysicalStart, NumberOfPages, Attribute\n");
+    for (i, desc) in memory_map.entries().enumerate() {
+        writeln!(
+            csv,
+            "{}, {:x}, {:?}, {:08x}, {:x}, {:x}",
+            i,
+            desc.ty.0,
+            desc.ty,
+            desc.phys_start,
+            desc.page_count,
+            desc.att.bits()
+        )
+        .unwrap();
+    }
+    csv
+}
+
+/// Writes `memory_map` to `path` on the ESP, replacing any existing file.
+pub fn save(root_dir: &mut Directory, path: &str, memory_map: &impl MemoryMap) -> uefi::Result {
+    let csv = to_csv(memory_map);
+
+    let path = CString16::try_from(path).map_err(|_| uefi::Status::INVALID_PARAMETER)?;
+    // Creating a file doesn't truncate an existing one, so remove the old dump first.
+    if let Ok(old) = root_dir.open(&path, FileMode::ReadWrite, FileAttribute::empty()) {
+        old.delete()?;
+    }
+    let mut file = root_dir
+        .open(&path, FileMode::CreateReadWrite, FileAttribute::empty())?
+        .into_regular_file()
+        .ok_or(uefi::Status::INVALID_PARAMETER)?;
+    file.write(csv.as_bytes())
+        .map_err(|err| err.to_err_without_payload())?;
+    file.flush()
+}
+
+/// Logs the number of pages per memory type.
+pub fn log_summary(memory_map: &impl MemoryMap) {
+    let mut pages = BTreeMap::new();
+    for desc in memory_map.entries() {
+        *pages.entry(desc.ty.0).or_insert(0u64) += desc.page_count;
+    }
+    info!("Memory map: {} descriptors", memory_map.len());
+    for (ty, count) in pages {
+        info!(
+            "  {:<24} {:>8} pages ({} KiB)",
+            format!("{:?}", MemoryType(ty)),
+            count,
+            count * 4
+        );
+    }
+}
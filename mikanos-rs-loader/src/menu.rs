@@ -0,0 +1,110 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use uefi::boot::{self, EventType, TimerTrigger, Tpl};
+use uefi::proto::console::text::{Key, ScanCode};
+use uefi::proto::media::file::{Directory, File, FileAttribute, FileMode};
+use uefi::{CString16, ResultExt, println, system};
+
+pub struct MenuEntry {
+    /// File name, e.g. `kernel.elf`.
+    pub name: String,
+    /// Absolute path on the ESP, e.g. `\mikanos\kernels\kernel.elf`.
+    pub path: String,
+}
+
+/// Lists the `*.elf` files directly under `dir_path`, sorted by name.
+pub fn find_kernels(root_dir: &mut Directory, dir_path: &str) -> uefi::Result<Vec<MenuEntry>> {
+    let path = CString16::try_from(dir_path).map_err(|_| uefi::Status::INVALID_PARAMETER)?;
+    let mut dir = root_dir
+        .open(&path, FileMode::Read, FileAttribute::empty())?
+        .into_directory()
+        .ok_or(uefi::Status::INVALID_PARAMETER)?;
+    let mut entries = Vec::new();
+    while let Some(info) = dir.read_entry_boxed()? {
+        let name = info.file_name().to_string();
+        if info.is_directory() || !name.to_ascii_lowercase().ends_with(".elf") {
+            continue;
+        }
+        let path = alloc::format!("{}\\{}", dir_path.trim_end_matches('\\'), name);
+        entries.push(MenuEntry { name, path });
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+fn draw(entries: &[MenuEntry], selected: usize, remaining: Option<u64>) {
+    system::with_stdout(|stdout| stdout.clear()).unwrap();
+    println!("mikanos-rs boot menu (Up/Down or 1-9 to select, Enter to boot)");
+    println!();
+    for (i, entry) in entries.iter().enumerate() {
+        let marker = if i == selected { '>' } else { ' ' };
+        println!(" {} {}. {}", marker, i + 1, entry.name);
+    }
+    println!();
+    if let Some(remaining) = remaining {
+        println!(
+            "Booting {} in {} seconds...",
+            entries[selected].name, remaining
+        );
+    }
+}
+
+/// Lets the user pick an entry with the keyboard.
+///
+/// `default` is booted when nothing is pressed within `timeout_secs` seconds. Any key press stops
+/// the countdown.
+pub fn select(entries: &[MenuEntry], default: usize, timeout_secs: u64) -> usize {
+    let mut selected = default;
+    let mut remaining = Some(timeout_secs);
+
+    let timer = unsafe { boot::create_event(EventType::TIMER, Tpl::APPLICATION, None, None) }
+        .expect("Failed to create timer event.");
+    // The trigger time is in units of 100ns.
+    boot::set_timer(&timer, TimerTrigger::Periodic(10_000_000)).expect("Failed to set timer.");
+
+    loop {
+        draw(entries, selected, remaining);
+        if remaining == Some(0) {
+            break;
+        }
+
+        let key_event = system::with_stdin(|stdin| stdin.wait_for_key_event())
+            .expect("Keyboard input is not available.");
+        let mut events = [key_event, unsafe { timer.unsafe_clone() }];
+        let index = boot::wait_for_event(&mut events)
+            .discard_errdata()
+            .expect("Failed to wait for events.");
+        if index == 1 {
+            if let Some(remaining) = remaining.as_mut() {
+                *remaining -= 1;
+            }
+            continue;
+        }
+
+        remaining = None;
+        let key = system::with_stdin(|stdin| stdin.read_key()).unwrap_or(None);
+        match key {
+            Some(Key::Special(ScanCode::UP)) => {
+                selected = (selected + entries.len() - 1) % entries.len();
+            }
+            Some(Key::Special(ScanCode::DOWN)) => {
+                selected = (selected + 1) % entries.len();
+            }
+            Some(Key::Printable(c)) => match char::from(c) {
+                '\r' | '\n' => break,
+                c @ '1'..='9' => {
+                    let index = c as usize - '1' as usize;
+                    if index < entries.len() {
+                        selected = index;
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    boot::close_event(timer).expect("Failed to close timer event.");
+    selected
+}
@@ -0,0 +1,5 @@
+//! Text processing for the kernel console that doesn't depend on the frame buffer.
+#![cfg_attr(not(test), no_std)]
+
+pub mod unicode;
+pub mod vt100;
@@ -0,0 +1,177 @@
+//! Character widths for the console's cell grid, after Unicode's East Asian Width property.
+//!
+//! The tables cover the common blocks rather than the whole Unicode database: CJK, Hangul, kana,
+//! fullwidth forms and the main emoji blocks are double width, combining marks and format
+//! characters take no cell.
+
+/// Inclusive code point ranges, sorted, of characters that take no cell.
+const ZERO_WIDTH: &[(u32, u32)] = &[
+    (0x0000, 0x001f),
+    (0x007f, 0x009f),
+    (0x0300, 0x036f),
+    (0x0483, 0x0489),
+    (0x0591, 0x05bd),
+    (0x0610, 0x061a),
+    (0x064b, 0x065f),
+    (0x1ab0, 0x1aff),
+    (0x1dc0, 0x1dff),
+    (0x200b, 0x200f),
+    (0x202a, 0x202e),
+    (0x2060, 0x2064),
+    (0x20d0, 0x20ff),
+    // Combining dakuten and handakuten.
+    (0x3099, 0x309a),
+    (0xfe00, 0xfe0f),
+    (0xfe20, 0xfe2f),
+    (0xfeff, 0xfeff),
+    (0xe0100, 0xe01ef),
+];
+
+/// Inclusive code point ranges, sorted, of characters that take two cells.
+const WIDE: &[(u32, u32)] = &[
+    // Hangul Jamo initial consonants.
+    (0x1100, 0x115f),
+    (0x2329, 0x232a),
+    // CJK radicals, ideographic description characters, and CJK symbols and punctuation.
+    (0x2e80, 0x303e),
+    // Hiragana, katakana, bopomofo, Hangul compatibility Jamo and CJK compatibility.
+    (0x3041, 0x33ff),
+    // CJK unified ideographs extension A.
+    (0x3400, 0x4dbf),
+    // CJK unified ideographs.
+    (0x4e00, 0x9fff),
+    // Yi.
+    (0xa000, 0xa4cf),
+    (0xa960, 0xa97f),
+    // Hangul syllables.
+    (0xac00, 0xd7a3),
+    // CJK compatibility ideographs.
+    (0xf900, 0xfaff),
+    // Vertical forms.
+    (0xfe10, 0xfe19),
+    // CJK compatibility forms and small form variants.
+    (0xfe30, 0xfe6f),
+    // Fullwidth ASCII and punctuation.
+    (0xff00, 0xff60),
+    (0xffe0, 0xffe6),
+    // Miscellaneous symbols and pictographs, and emoticons.
+    (0x1f300, 0x1f64f),
+    // Supplemental symbols and pictographs.
+    (0x1f900, 0x1f9ff),
+    // CJK unified ideographs extension B and later.
+    (0x20000, 0x2fffd),
+    (0x30000, 0x3fffd),
+];
+
+fn contains(table: &[(u32, u32)], c: char) -> bool {
+    let c = c as u32;
+    table
+        .binary_search_by(|&(start, end)| {
+            if end < c {
+                core::cmp::Ordering::Less
+            } else if start > c {
+                core::cmp::Ordering::Greater
+            } else {
+                core::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+/// Returns the number of cells `c` takes: 0, 1 or 2.
+pub fn char_width(c: char) -> usize {
+    if c.is_ascii_graphic() || c == ' ' {
+        1
+    } else if contains(ZERO_WIDTH, c) {
+        0
+    } else if contains(WIDE, c) {
+        2
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn widths(chars: &[char]) -> Vec<usize> {
+        chars.iter().map(|&c| char_width(c)).collect()
+    }
+
+    #[test]
+    fn tables_are_sorted() {
+        for table in [ZERO_WIDTH, WIDE] {
+            for &(start, end) in table {
+                assert!(start <= end, "{:#x}..={:#x}", start, end);
+            }
+            for pair in table.windows(2) {
+                assert!(pair[0].1 < pair[1].0, "{:x?}", pair);
+            }
+        }
+    }
+
+    #[test]
+    fn ascii() {
+        assert_eq!(widths(&[' ', '!', 'A', 'z', '~']), [1; 5]);
+        assert_eq!(widths(&['\0', '\n', '\x1b', '\x7f']), [0; 4]);
+        // Latin-1, Greek and Cyrillic letters and box drawing are narrow.
+        assert_eq!(widths(&['\u{a0}', 'é', 'Ω', 'Ж', '─']), [1; 5]);
+    }
+
+    #[test]
+    fn combining_marks() {
+        assert_eq!(widths(&['\u{2ff}', '\u{300}', '\u{36f}', '\u{370}']), [
+            1, 0, 0, 1
+        ]);
+        // Combining dakuten, zero width space and joiner, BOM and variation selectors.
+        assert_eq!(
+            widths(&[
+                '\u{3099}', '\u{309a}', '\u{200b}', '\u{200d}', '\u{feff}', '\u{fe0f}'
+            ]),
+            [0; 6]
+        );
+        assert_eq!(widths(&['\u{e0100}', '\u{e01ef}', '\u{e01f0}']), [0, 0, 1]);
+    }
+
+    #[test]
+    fn cjk() {
+        // Around the ideographic space and CJK symbols, kana and unified ideographs.
+        assert_eq!(
+            widths(&['\u{2e7f}', '\u{2e80}', '\u{3000}', '\u{303e}', '\u{303f}']),
+            [1, 2, 2, 2, 1]
+        );
+        assert_eq!(widths(&['あ', 'ア', 'ー', '一', '漢', '\u{9fff}']), [2; 6]);
+        assert_eq!(widths(&['\u{a4cf}', '\u{a4d0}']), [2, 1]);
+        assert_eq!(widths(&['\u{20000}', '\u{2fffd}', '\u{2fffe}']), [2, 2, 1]);
+    }
+
+    #[test]
+    fn hangul() {
+        assert_eq!(widths(&['\u{10ff}', '\u{1100}', '\u{115f}', '\u{1160}']), [
+            1, 2, 2, 1
+        ]);
+        assert_eq!(widths(&['\u{abff}', '가', '힣', '\u{d7a4}']), [
+            1, 2, 2, 1
+        ]);
+    }
+
+    #[test]
+    fn fullwidth_forms() {
+        assert_eq!(
+            widths(&['\u{feff}', '\u{ff00}', 'Ａ', '\u{ff60}', '\u{ff61}', 'ｱ']),
+            [0, 2, 2, 2, 1, 1]
+        );
+        assert_eq!(widths(&['\u{ffdf}', '\u{ffe0}', '\u{ffe6}', '\u{ffe7}']), [
+            1, 2, 2, 1
+        ]);
+    }
+
+    #[test]
+    fn emoji() {
+        assert_eq!(
+            widths(&['\u{1f2ff}', '🌀', '😀', '\u{1f64f}', '\u{1f650}']),
+            [1, 2, 2, 2, 1]
+        );
+    }
+}
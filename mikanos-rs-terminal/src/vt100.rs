@@ -0,0 +1,438 @@
+//! A parser for the subset of VT100/ANSI escape sequences the kernel console understands.
+//!
+//! Based on the state machine at <https://vt100.net/emu/dec_ansi_parser>, without OSC/DCS
+//! strings, which are skipped.
+//!
+//! The parser works on characters rather than bytes, so UTF-8 is decoded before it, e.g. by
+//! `str::chars`.
+
+const ESC: u8 = 0x1b;
+const CAN: u8 = 0x18;
+const SUB: u8 = 0x1a;
+const MAX_PARAMS: usize = 16;
+
+/// A control sequence such as `ESC [ 1 ; 31 m`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Csi {
+    params: [u16; MAX_PARAMS],
+    num_params: usize,
+    /// Set for DEC private sequences such as `ESC [ ? 25 l`.
+    pub private: bool,
+    pub final_byte: u8,
+}
+
+impl Csi {
+    const fn new() -> Self {
+        Self {
+            params: [0; MAX_PARAMS],
+            num_params: 0,
+            private: false,
+            final_byte: 0,
+        }
+    }
+
+    /// The parameters as given. Omitted ones are 0.
+    pub fn params(&self) -> &[u16] {
+        &self.params[..self.num_params]
+    }
+
+    /// Returns parameter `i`, or `default` if it is omitted or 0.
+    pub fn param(&self, i: usize, default: u16) -> u16 {
+        match self.params().get(i) {
+            Some(&value) if value != 0 => value,
+            _ => default,
+        }
+    }
+
+    /// Decodes the parameters of an SGR sequence (`ESC [ ... m`).
+    pub fn sgr(&self) -> SgrIter<'_> {
+        let params = match self.params() {
+            [] => &[0],
+            params => params,
+        };
+        SgrIter { params }
+    }
+}
+
+/// A color selected by SGR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    /// An entry of the xterm 256-color palette. 0 to 15 are the 16 system colors.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// One attribute change of an SGR sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sgr {
+    Reset,
+    Bold(bool),
+    Reverse(bool),
+    /// `None` selects the default color.
+    Foreground(Option<Color>),
+    Background(Option<Color>),
+}
+
+/// Iterator over the attribute changes of an SGR sequence. Unsupported attributes are skipped.
+pub struct SgrIter<'a> {
+    params: &'a [u16],
+}
+
+impl Iterator for SgrIter<'_> {
+    type Item = Sgr;
+
+    fn next(&mut self) -> Option<Sgr> {
+        loop {
+            let (&param, rest) = self.params.split_first()?;
+            self.params = rest;
+            return Some(match param {
+                0 => Sgr::Reset,
+                1 => Sgr::Bold(true),
+                22 => Sgr::Bold(false),
+                7 => Sgr::Reverse(true),
+                27 => Sgr::Reverse(false),
+                n @ 30..=37 => Sgr::Foreground(Some(Color::Indexed(n as u8 - 30))),
+                n @ 40..=47 => Sgr::Background(Some(Color::Indexed(n as u8 - 40))),
+                n @ 90..=97 => Sgr::Foreground(Some(Color::Indexed(n as u8 - 90 + 8))),
+                n @ 100..=107 => Sgr::Background(Some(Color::Indexed(n as u8 - 100 + 8))),
+                39 => Sgr::Foreground(None),
+                49 => Sgr::Background(None),
+                n @ (38 | 48) => {
+                    let Some((color, len)) = extended_color(self.params) else {
+                        // The rest can't be interpreted without knowing the format.
+                        self.params = &[];
+                        return None;
+                    };
+                    self.params = &self.params[len..];
+                    if n == 38 {
+                        Sgr::Foreground(Some(color))
+                    } else {
+                        Sgr::Background(Some(color))
+                    }
+                }
+                _ => continue,
+            });
+        }
+    }
+}
+
+/// Parses the color after SGR 38 or 48: `5;n` from the 256-color palette or `2;r;g;b`. Returns the
+/// color and the number of parameters used.
+fn extended_color(params: &[u16]) -> Option<(Color, usize)> {
+    let channel = |value: u16| value.min(255) as u8;
+    match *params {
+        [5, index, ..] => Some((Color::Indexed(channel(index)), 2)),
+        [2, r, g, b, ..] => Some((Color::Rgb(channel(r), channel(g), channel(b)), 4)),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// A character to draw.
+    Print(char),
+    /// A C0 control character such as `\n` or `\r`.
+    Execute(u8),
+    /// An escape sequence without parameters, e.g. `ESC 7`.
+    Esc(u8),
+    Csi(Csi),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    CsiParam,
+    /// Inside a malformed or unsupported control sequence, waiting for its end.
+    CsiIgnore,
+    /// Inside an OSC, DCS or similar string, waiting for `ESC \` or BEL.
+    String,
+}
+
+pub struct Parser {
+    state: State,
+    csi: Csi,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Ground,
+            csi: Csi::new(),
+        }
+    }
+
+    /// Feeds one character. Returns what to do once a character or sequence is complete.
+    pub fn advance(&mut self, c: char) -> Option<Action> {
+        if !c.is_ascii() {
+            // Sequences consist of ASCII only, so anything else is text or a malformed sequence.
+            return match self.state {
+                State::Ground => Some(Action::Print(c)),
+                State::Escape => {
+                    self.state = State::Ground;
+                    None
+                }
+                State::CsiParam | State::CsiIgnore => {
+                    self.state = State::CsiIgnore;
+                    None
+                }
+                State::String => None,
+            };
+        }
+        let byte = c as u8;
+        match (self.state, byte) {
+            (State::String, 0x07) => {
+                self.state = State::Ground;
+                None
+            }
+            (State::String, ESC) => {
+                // The `\` of the string terminator is then taken as an unknown escape.
+                self.state = State::Escape;
+                None
+            }
+            (State::String, _) => None,
+            (_, CAN | SUB) => {
+                self.state = State::Ground;
+                None
+            }
+            (_, ESC) => {
+                self.state = State::Escape;
+                None
+            }
+            (_, 0x00..=0x1f) => Some(Action::Execute(byte)),
+            (_, 0x7f) => None,
+            (State::Ground, _) => Some(Action::Print(c)),
+            (State::Escape, b'[') => {
+                self.csi = Csi::new();
+                self.state = State::CsiParam;
+                None
+            }
+            (State::Escape, b']' | b'P' | b'X' | b'^' | b'_') => {
+                self.state = State::String;
+                None
+            }
+            // Intermediate bytes, e.g. character set selection `ESC ( B`. Not supported.
+            (State::Escape, 0x20..=0x2f) => None,
+            (State::Escape, _) => {
+                self.state = State::Ground;
+                Some(Action::Esc(byte))
+            }
+            (State::CsiParam, b'0'..=b'9') => {
+                if self.csi.num_params == 0 {
+                    self.csi.num_params = 1;
+                }
+                let param = &mut self.csi.params[self.csi.num_params - 1];
+                *param = param
+                    .saturating_mul(10)
+                    .saturating_add((byte - b'0') as u16);
+                None
+            }
+            (State::CsiParam, b';' | b':') => {
+                if self.csi.num_params == 0 {
+                    self.csi.num_params = 1;
+                }
+                if self.csi.num_params == MAX_PARAMS {
+                    self.state = State::CsiIgnore;
+                } else {
+                    self.csi.num_params += 1;
+                }
+                None
+            }
+            (State::CsiParam, b'?') if self.csi.num_params == 0 => {
+                self.csi.private = true;
+                None
+            }
+            (State::CsiParam | State::CsiIgnore, 0x40..=0x7e) => {
+                let ignored = self.state == State::CsiIgnore;
+                self.state = State::Ground;
+                self.csi.final_byte = byte;
+                (!ignored).then_some(Action::Csi(self.csi))
+            }
+            (State::CsiParam, _) => {
+                self.state = State::CsiIgnore;
+                None
+            }
+            (State::CsiIgnore, _) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `chunks` to one parser, as consecutive `put_string` calls of the console do.
+    fn parse(chunks: &[&str]) -> Vec<Action> {
+        let mut parser = Parser::new();
+        chunks
+            .iter()
+            .flat_map(|chunk| chunk.chars())
+            .filter_map(|c| parser.advance(c))
+            .collect()
+    }
+
+    /// Parses `s`, which must consist of exactly one control sequence.
+    fn csi(s: &str) -> Csi {
+        match parse(&[s])[..] {
+            [Action::Csi(csi)] => csi,
+            ref actions => panic!("{:?}: expected one CSI, got {:?}", s, actions),
+        }
+    }
+
+    fn sgr(s: &str) -> Vec<Sgr> {
+        csi(s).sgr().collect()
+    }
+
+    #[test]
+    fn prints_text_and_executes_controls() {
+        assert_eq!(parse(&["a\r\n\tあ\x7f"]), [
+            Action::Print('a'),
+            Action::Execute(b'\r'),
+            Action::Execute(b'\n'),
+            Action::Execute(b'\t'),
+            Action::Print('あ'),
+        ]);
+    }
+
+    #[test]
+    fn parses_cursor_moves() {
+        let cup = csi("\x1b[5;10H");
+        assert_eq!((cup.params(), cup.final_byte), (&[5, 10][..], b'H'));
+        assert_eq!((cup.param(0, 1), cup.param(1, 1)), (5, 10));
+
+        // Omitted and 0 parameters both mean the default.
+        let home = csi("\x1b[H");
+        assert!(home.params().is_empty());
+        assert_eq!((home.param(0, 1), home.param(1, 1)), (1, 1));
+        let cup = csi("\x1b[0;7f");
+        assert_eq!((cup.param(0, 1), cup.param(1, 1)), (1, 7));
+        let cup = csi("\x1b[;7H");
+        assert_eq!(cup.params(), [0, 7]);
+        assert_eq!((cup.param(0, 1), cup.param(1, 1)), (1, 7));
+
+        for (s, final_byte, n) in [
+            ("\x1b[3A", b'A', 3),
+            ("\x1b[B", b'B', 1),
+            ("\x1b[0C", b'C', 1),
+            ("\x1b[12D", b'D', 12),
+        ] {
+            let csi = csi(s);
+            assert_eq!(
+                (csi.final_byte, csi.param(0, 1)),
+                (final_byte, n),
+                "{:?}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn parses_erase() {
+        for (s, final_byte, mode) in [
+            ("\x1b[K", b'K', 0),
+            ("\x1b[1K", b'K', 1),
+            ("\x1b[2K", b'K', 2),
+            ("\x1b[J", b'J', 0),
+            ("\x1b[2J", b'J', 2),
+            ("\x1b[3J", b'J', 3),
+        ] {
+            let csi = csi(s);
+            assert_eq!(
+                (csi.final_byte, csi.param(0, 0)),
+                (final_byte, mode),
+                "{:?}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn parses_private_sequences() {
+        let csi = csi("\x1b[?25l");
+        assert!(csi.private);
+        assert_eq!((csi.params(), csi.final_byte), (&[25][..], b'l'));
+    }
+
+    #[test]
+    fn saturates_large_parameters() {
+        assert_eq!(csi("\x1b[99999999H").params(), [u16::MAX]);
+    }
+
+    #[test]
+    fn decodes_sgr_with_multiple_parameters() {
+        assert_eq!(sgr("\x1b[m"), [Sgr::Reset]);
+        assert_eq!(sgr("\x1b[;1m"), [Sgr::Reset, Sgr::Bold(true)]);
+        assert_eq!(sgr("\x1b[1;31;42;97;104m"), [
+            Sgr::Bold(true),
+            Sgr::Foreground(Some(Color::Indexed(1))),
+            Sgr::Background(Some(Color::Indexed(2))),
+            Sgr::Foreground(Some(Color::Indexed(15))),
+            Sgr::Background(Some(Color::Indexed(12))),
+        ]);
+        assert_eq!(sgr("\x1b[22;7;27;39;49;0m"), [
+            Sgr::Bold(false),
+            Sgr::Reverse(true),
+            Sgr::Reverse(false),
+            Sgr::Foreground(None),
+            Sgr::Background(None),
+            Sgr::Reset,
+        ]);
+        // Underline and blink aren't supported.
+        assert_eq!(sgr("\x1b[4;5;33m"), [Sgr::Foreground(Some(
+            Color::Indexed(3)
+        ))]);
+    }
+
+    #[test]
+    fn decodes_sgr_extended_colors() {
+        assert_eq!(sgr("\x1b[38;5;208;48;2;10;20;300;1m"), [
+            Sgr::Foreground(Some(Color::Indexed(208))),
+            Sgr::Background(Some(Color::Rgb(10, 20, 255))),
+            Sgr::Bold(true),
+        ]);
+        assert_eq!(sgr("\x1b[48:5:17m"), [Sgr::Background(Some(
+            Color::Indexed(17)
+        ))]);
+        // An unknown or truncated color format ends the sequence.
+        assert_eq!(sgr("\x1b[1;38;9;31m"), [Sgr::Bold(true)]);
+        assert_eq!(sgr("\x1b[31;48;2;1;2m"), [Sgr::Foreground(Some(
+            Color::Indexed(1)
+        ))]);
+    }
+
+    #[test]
+    fn keeps_state_across_calls() {
+        assert_eq!(parse(&["ab\x1b", "[3", "1;4", "2m", "c"]), [
+            Action::Print('a'),
+            Action::Print('b'),
+            Action::Csi(csi("\x1b[31;42m")),
+            Action::Print('c'),
+        ]);
+        assert_eq!(parse(&["\x1b]0;ti", "tle\x07", "ok"]), [
+            Action::Print('o'),
+            Action::Print('k')
+        ]);
+        assert_eq!(parse(&["\x1b", "7"]), [Action::Esc(b'7')]);
+    }
+
+    #[test]
+    fn skips_malformed_sequences() {
+        // CAN cancels a sequence, so its final byte is printed.
+        assert_eq!(parse(&["\x1b[31\x18m"]), [Action::Print('m')]);
+        // Non-ASCII characters and too many parameters invalidate the sequence up to its end.
+        assert_eq!(parse(&["\x1b[3あ1mx"]), [Action::Print('x')]);
+        let too_many = format!("\x1b[{}mx", "1;".repeat(MAX_PARAMS));
+        assert_eq!(parse(&[&too_many]), [Action::Print('x')]);
+        // A string terminated by ESC \ leaves the `\` as an unknown escape.
+        assert_eq!(parse(&["\x1bPdata\x1b\\y"]), [
+            Action::Esc(b'\\'),
+            Action::Print('y')
+        ]);
+    }
+}
@@ -50,6 +50,10 @@ fi
 
 # Make EFI system partition
 mkdir -p esp/efi/boot
+if [ -d initrd ]; then
+  mkdir -p esp/mikanos
+  tar --format=ustar -cf esp/mikanos/initrd.tar -C initrd .
+fi
 cp target/x86_64-unknown-uefi/debug/mikanos-rs-loader.efi esp/efi/boot/bootx64.efi
 cp target/x86_64-unknown-none/debug/mikanos-rs-kernel esp/kernel.elf
 
@@ -0,0 +1,78 @@
+#!/usr/bin/env python3
+"""Extracts screenshots dumped by the kernel from a serial log.
+
+The kernel writes a base64-encoded PPM between marker lines when Print Screen is pressed or the
+`screenshot` debug command is typed into the serial console. The data lines start with `|`; other
+kernel output may be interleaved with them. Usage:
+
+    bash run.sh | tee serial.log
+    python3 tools/extract-screenshots.py serial.log [-o DIR] [--png]
+"""
+import argparse
+import base64
+import pathlib
+import struct
+import sys
+import zlib
+
+BEGIN_MARKER = "-----BEGIN MIKANOS SCREENSHOT-----"
+END_MARKER = "-----END MIKANOS SCREENSHOT-----"
+DATA_PREFIX = "|"
+
+
+def extract(lines):
+    """Yields the decoded images in the log."""
+    body = None
+    for line in lines:
+        line = line.strip()
+        if line == BEGIN_MARKER:
+            body = []
+        elif line == END_MARKER and body is not None:
+            yield base64.b64decode("".join(body))
+            body = None
+        elif body is not None and line.startswith(DATA_PREFIX):
+            body.append(line[len(DATA_PREFIX):])
+
+
+def ppm_to_png(ppm):
+    magic, width, height, max_value, pixels = ppm.split(maxsplit=4)
+    if magic != b"P6" or max_value != b"255":
+        raise ValueError("not an 8-bit binary PPM")
+    width, height = int(width), int(height)
+    stride = 3 * width
+    rows = b"".join(b"\0" + pixels[y * stride:(y + 1) * stride] for y in range(height))
+
+    def chunk(kind, data):
+        return struct.pack(">I", len(data)) + kind + data + struct.pack(">I", zlib.crc32(kind + data))
+
+    header = struct.pack(">IIBBBBB", width, height, 8, 2, 0, 0, 0)
+    return (b"\x89PNG\r\n\x1a\n" + chunk(b"IHDR", header) + chunk(b"IDAT", zlib.compress(rows))
+            + chunk(b"IEND", b""))
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("log", nargs="?", help="serial log (default: stdin)")
+    parser.add_argument("-o", "--output-dir", default=".", help="where to write the images")
+    parser.add_argument("--png", action="store_true", help="convert the images to PNG")
+    args = parser.parse_args()
+
+    log = open(args.log, errors="replace") if args.log else sys.stdin
+    output_dir = pathlib.Path(args.output_dir)
+    output_dir.mkdir(parents=True, exist_ok=True)
+    count = 0
+    for count, ppm in enumerate(extract(log), 1):
+        if args.png:
+            path = output_dir / f"screenshot-{count}.png"
+            path.write_bytes(ppm_to_png(ppm))
+        else:
+            path = output_dir / f"screenshot-{count}.ppm"
+            path.write_bytes(ppm)
+        print(path)
+    if count == 0:
+        print("No screenshots found.", file=sys.stderr)
+        sys.exit(1)
+
+
+if __name__ == "__main__":
+    main()